@@ -118,6 +118,10 @@ func (s *Coordinator) GetCollections(ctx context.Context, collectionID types.Uni
 	return s.catalog.GetCollections(ctx, collectionID, collectionName, tenantID, databaseName, limit, offset)
 }
 
+func (s *Coordinator) GetCollectionsByIds(ctx context.Context, collectionIDs []types.UniqueID) ([]*model.Collection, error) {
+	return s.catalog.GetCollectionsByIds(ctx, collectionIDs)
+}
+
 func (s *Coordinator) CountCollections(ctx context.Context, tenantID string, databaseName *string) (uint64, error) {
 	return s.catalog.CountCollections(ctx, tenantID, databaseName)
 }
@@ -381,6 +381,25 @@ func (tc *Catalog) GetCollections(ctx context.Context, collectionID types.Unique
 	return collections, nil
 }
 
+func (tc *Catalog) GetCollectionsByIds(ctx context.Context, collectionIDs []types.UniqueID) ([]*model.Collection, error) {
+	tracer := otel.Tracer
+	if tracer != nil {
+		_, span := tracer.Start(ctx, "Catalog.GetCollectionsByIds")
+		defer span.End()
+	}
+
+	ids := make([]string, 0, len(collectionIDs))
+	for _, collectionID := range collectionIDs {
+		ids = append(ids, collectionID.String())
+	}
+	collectionAndMetadataList, err := tc.metaDomain.CollectionDb(ctx).GetCollectionsByIds(ids)
+	if err != nil {
+		return nil, err
+	}
+	collections := convertCollectionToModel(collectionAndMetadataList)
+	return collections, nil
+}
+
 func (tc *Catalog) CountCollections(ctx context.Context, tenantID string, databaseName *string) (uint64, error) {
 	tracer := otel.Tracer
 	if tracer != nil {
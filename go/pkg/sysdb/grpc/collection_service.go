@@ -3,6 +3,7 @@ package grpc
 import (
 	"context"
 	"encoding/json"
+	"errors"
 	"fmt"
 
 	"github.com/chroma-core/chroma/go/pkg/grpcutils"
@@ -151,6 +152,31 @@ func (s *Server) GetCollections(ctx context.Context, req *coordinatorpb.GetColle
 	return res, nil
 }
 
+func (s *Server) GetCollectionsByIds(ctx context.Context, req *coordinatorpb.GetCollectionsByIdsRequest) (*coordinatorpb.GetCollectionsByIdsResponse, error) {
+	res := &coordinatorpb.GetCollectionsByIdsResponse{}
+
+	collectionIDs := make([]types.UniqueID, 0, len(req.CollectionIds))
+	for _, collectionID := range req.CollectionIds {
+		parsedCollectionID, err := types.ToUniqueID(&collectionID)
+		if err != nil {
+			log.Error("GetCollectionsByIds failed. collection id format error", zap.Error(err), zap.String("collection_id", collectionID))
+			return res, grpcutils.BuildInternalGrpcError(err.Error())
+		}
+		collectionIDs = append(collectionIDs, parsedCollectionID)
+	}
+
+	collections, err := s.coordinator.GetCollectionsByIds(ctx, collectionIDs)
+	if err != nil {
+		log.Error("GetCollectionsByIds failed. ", zap.Error(err), zap.Strings("collection_ids", req.CollectionIds))
+		return res, grpcutils.BuildInternalGrpcError(err.Error())
+	}
+	res.Collections = make([]*coordinatorpb.Collection, 0, len(collections))
+	for _, collection := range collections {
+		res.Collections = append(res.Collections, convertCollectionToProto(collection))
+	}
+	return res, nil
+}
+
 func (s *Server) CountCollections(ctx context.Context, req *coordinatorpb.CountCollectionsRequest) (*coordinatorpb.CountCollectionsResponse, error) {
 	res := &coordinatorpb.CountCollectionsResponse{}
 	collection_count, err := s.coordinator.CountCollections(ctx, req.Tenant, req.Database)
@@ -319,7 +345,7 @@ func (s *Server) UpdateCollection(ctx context.Context, req *coordinatorpb.Update
 		}
 	}
 
-	_, err = s.coordinator.UpdateCollection(ctx, updateCollection)
+	collection, err := s.coordinator.UpdateCollection(ctx, updateCollection)
 
 	if err != nil {
 		log.Error("UpdateCollection failed. error updating collection", zap.Error(err), zap.String("collection_id", collectionID))
@@ -329,6 +355,7 @@ func (s *Server) UpdateCollection(ctx context.Context, req *coordinatorpb.Update
 		return res, grpcutils.BuildInternalGrpcError(err.Error())
 	}
 
+	res.Collection = convertCollectionToProto(collection)
 	return res, nil
 }
 
@@ -387,6 +414,9 @@ func (s *Server) FlushCollectionCompaction(ctx context.Context, req *coordinator
 	flushCollectionInfo, err := s.coordinator.FlushCollectionCompaction(ctx, FlushCollectionCompaction)
 	if err != nil {
 		log.Error("FlushCollectionCompaction failed", zap.Error(err), zap.String("collection_id", req.CollectionId), zap.Int32("collection_version", req.CollectionVersion), zap.Int64("log_position", req.LogPosition))
+		if errors.Is(err, common.ErrCollectionVersionStale) {
+			return nil, grpcutils.BuildFailedPreconditionGrpcError(err.Error())
+		}
 		return nil, grpcutils.BuildInternalGrpcError(err.Error())
 	}
 	res := &coordinatorpb.FlushCollectionCompactionResponse{
@@ -448,7 +448,7 @@ func (suite *CollectionServiceTestSuite) TestServer_FlushCollectionCompaction()
 	}
 	response, err = suite.s.FlushCollectionCompaction(context.Background(), req)
 	suite.Error(err)
-	suite.Equal(status.Error(codes.Code(code.Code_INTERNAL), common.ErrCollectionVersionStale.Error()), err)
+	suite.Equal(status.Error(codes.Code(code.Code_FAILED_PRECONDITION), common.ErrCollectionVersionStale.Error()), err)
 	// nothing should change in DB
 	validateDatabase(suite, collectionID, collection, filePaths)
 
@@ -136,6 +136,9 @@ func (s *Server) SetLastCompactionTimeForTenant(ctx context.Context, req *coordi
 	err := s.coordinator.SetTenantLastCompactionTime(ctx, req.TenantLastCompactionTime.TenantId, req.TenantLastCompactionTime.LastCompactionTime)
 	if err != nil {
 		log.Error("error SetTenantLastCompactionTime", zap.String("request", req.String()), zap.Error(err))
+		if errors.Is(err, common.ErrTenantNotFound) {
+			return nil, grpcutils.BuildNotFoundGrpcError(err.Error())
+		}
 		return nil, grpcutils.BuildInternalGrpcError(err.Error())
 	}
 	log.Info("SetLastCompactionTimeForTenant success", zap.String("request", req.String()))
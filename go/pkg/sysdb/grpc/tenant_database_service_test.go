@@ -63,7 +63,7 @@ func (suite *TenantDatabaseServiceTestSuite) TestServer_TenantLastCompactionTime
 		},
 	}
 	_, err := suite.s.SetLastCompactionTimeForTenant(context.Background(), request)
-	suite.Equal(status.Error(codes.Code(code.Code_INTERNAL), common.ErrTenantNotFound.Error()), err)
+	suite.Equal(status.Error(codes.Code(code.Code_NOT_FOUND), common.ErrTenantNotFound.Error()), err)
 
 	// create tenant
 	_, err = suite.catalog.CreateTenant(context.Background(), &model.CreateTenant{
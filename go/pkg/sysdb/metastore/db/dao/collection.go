@@ -49,7 +49,20 @@ func (s *collectionDb) GetCollectionEntry(collectionID *string, databaseName *st
 }
 
 func (s *collectionDb) GetCollections(id *string, name *string, tenantID string, databaseName string, limit *int32, offset *int32) ([]*dbmodel.CollectionAndMetadata, error) {
-	return s.getCollections(id, name, tenantID, databaseName, limit, offset, false)
+	var ids []string
+	if id != nil {
+		ids = []string{*id}
+	}
+	return s.getCollections(ids, name, tenantID, databaseName, limit, offset, false)
+}
+
+// GetCollectionsByIds returns the live collections among ids with one query.
+// Ids that are not found are left out.
+func (s *collectionDb) GetCollectionsByIds(ids []string) ([]*dbmodel.CollectionAndMetadata, error) {
+	if len(ids) == 0 {
+		return []*dbmodel.CollectionAndMetadata{}, nil
+	}
+	return s.getCollections(ids, nil, "", "", nil, nil, false)
 }
 
 func (s *collectionDb) ListCollectionsToGc() ([]*dbmodel.CollectionToGc, error) {
@@ -64,7 +77,7 @@ func (s *collectionDb) ListCollectionsToGc() ([]*dbmodel.CollectionToGc, error)
 	return collections, nil
 }
 
-func (s *collectionDb) getCollections(id *string, name *string, tenantID string, databaseName string, limit *int32, offset *int32, is_deleted bool) (collectionWithMetdata []*dbmodel.CollectionAndMetadata, err error) {
+func (s *collectionDb) getCollections(ids []string, name *string, tenantID string, databaseName string, limit *int32, offset *int32, is_deleted bool) (collectionWithMetdata []*dbmodel.CollectionAndMetadata, err error) {
 	type Result struct {
 		// Collection fields
 		CollectionId               string     `gorm:"column:collection_id"`
@@ -106,8 +119,8 @@ func (s *collectionDb) getCollections(id *string, name *string, tenantID string,
 	if tenantID != "" {
 		query = query.Where("databases.tenant_id = ?", tenantID)
 	}
-	if id != nil {
-		query = query.Where("collections.id = ?", *id)
+	if ids != nil {
+		query = query.Where("collections.id IN ?", ids)
 	}
 	if name != nil {
 		query = query.Where("collections.name = ?", *name)
@@ -276,7 +289,11 @@ func (s *collectionDb) GetCollectionSize(id string) (uint64, error) {
 }
 
 func (s *collectionDb) GetSoftDeletedCollections(collectionID *string, tenantID string, databaseName string, limit int32) ([]*dbmodel.CollectionAndMetadata, error) {
-	return s.getCollections(collectionID, nil, tenantID, databaseName, &limit, nil, true)
+	var ids []string
+	if collectionID != nil {
+		ids = []string{*collectionID}
+	}
+	return s.getCollections(ids, nil, tenantID, databaseName, &limit, nil, true)
 }
 
 // NOTE: This is the only method to do a hard delete of a single collection.
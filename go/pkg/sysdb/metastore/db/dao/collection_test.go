@@ -10,6 +10,7 @@ import (
 	"github.com/stretchr/testify/suite"
 
 	"github.com/chroma-core/chroma/go/pkg/sysdb/metastore/db/dbmodel"
+	"github.com/chroma-core/chroma/go/pkg/types"
 	"gorm.io/gorm"
 )
 
@@ -272,6 +273,29 @@ func (suite *CollectionDbTestSuite) TestCollectionDb_GetCollectionSize() {
 	suite.NoError(err)
 }
 
+func (suite *CollectionDbTestSuite) TestCollectionDb_GetCollectionsByIds() {
+	collectionID1, err := CreateTestCollection(suite.db, "test_collection_get_by_ids1", 128, suite.databaseId)
+	suite.NoError(err)
+	collectionID2, err := CreateTestCollection(suite.db, "test_collection_get_by_ids2", 128, suite.databaseId)
+	suite.NoError(err)
+
+	// Unknown ids are left out.
+	collections, err := suite.collectionDb.GetCollectionsByIds([]string{collectionID2, types.NewUniqueID().String(), collectionID1})
+	suite.NoError(err)
+	suite.Len(collections, 2)
+	ids := []string{collections[0].Collection.ID, collections[1].Collection.ID}
+	suite.ElementsMatch([]string{collectionID1, collectionID2}, ids)
+
+	collections, err = suite.collectionDb.GetCollectionsByIds([]string{})
+	suite.NoError(err)
+	suite.Len(collections, 0)
+
+	err = CleanUpTestCollection(suite.db, collectionID1)
+	suite.NoError(err)
+	err = CleanUpTestCollection(suite.db, collectionID2)
+	suite.NoError(err)
+}
+
 func TestCollectionDbTestSuiteSuite(t *testing.T) {
 	testSuite := new(CollectionDbTestSuite)
 	suite.Run(t, testSuite)
@@ -47,6 +47,7 @@ type CollectionAndMetadata struct {
 //go:generate mockery --name=ICollectionDb
 type ICollectionDb interface {
 	GetCollections(collectionID *string, collectionName *string, tenantID string, databaseName string, limit *int32, offset *int32) ([]*CollectionAndMetadata, error)
+	GetCollectionsByIds(collectionIDs []string) ([]*CollectionAndMetadata, error)
 	CountCollections(tenantID string, databaseName *string) (uint64, error)
 	DeleteCollectionByID(collectionID string) (int, error)
 	GetSoftDeletedCollections(collectionID *string, tenantID string, databaseName string, limit int32) ([]*CollectionAndMetadata, error)
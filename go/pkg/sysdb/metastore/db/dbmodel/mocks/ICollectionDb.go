@@ -174,6 +174,36 @@ func (_m *ICollectionDb) GetCollections(collectionID *string, collectionName *st
 	return r0, r1
 }
 
+// GetCollectionsByIds provides a mock function with given fields: collectionIDs
+func (_m *ICollectionDb) GetCollectionsByIds(collectionIDs []string) ([]*dbmodel.CollectionAndMetadata, error) {
+	ret := _m.Called(collectionIDs)
+
+	if len(ret) == 0 {
+		panic("no return value specified for GetCollectionsByIds")
+	}
+
+	var r0 []*dbmodel.CollectionAndMetadata
+	var r1 error
+	if rf, ok := ret.Get(0).(func([]string) ([]*dbmodel.CollectionAndMetadata, error)); ok {
+		return rf(collectionIDs)
+	}
+	if rf, ok := ret.Get(0).(func([]string) []*dbmodel.CollectionAndMetadata); ok {
+		r0 = rf(collectionIDs)
+	} else {
+		if ret.Get(0) != nil {
+			r0 = ret.Get(0).([]*dbmodel.CollectionAndMetadata)
+		}
+	}
+
+	if rf, ok := ret.Get(1).(func([]string) error); ok {
+		r1 = rf(collectionIDs)
+	} else {
+		r1 = ret.Error(1)
+	}
+
+	return r0, r1
+}
+
 // GetSoftDeletedCollections provides a mock function with given fields: collectionID, tenantID, databaseName, limit
 func (_m *ICollectionDb) GetSoftDeletedCollections(collectionID *string, tenantID string, databaseName string, limit int32) ([]*dbmodel.CollectionAndMetadata, error) {
 	ret := _m.Called(collectionID, tenantID, databaseName, limit)
@@ -1,5 +1,6 @@
 pub mod assignment;
 pub mod registry;
+pub mod retry;
 
 use async_trait::async_trait;
 use chroma_error::ChromaError;
@@ -0,0 +1,46 @@
+use backon::ExponentialBuilder;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Configuration for an exponential backoff retry policy.
+/// # Fields
+/// - `factor` - The factor to multiply the delay by
+/// - `min_delay_ms` - The minimum delay in milliseconds
+/// - `max_delay_ms` - The maximum delay in milliseconds
+/// - `max_attempts` - The maximum number of retries made after the first attempt fails
+/// - `jitter` - Whether to randomize the delay between retries
+#[derive(Deserialize, Clone, Serialize, Debug)]
+pub struct RetryConfig {
+    pub factor: f32,
+    pub min_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub max_attempts: usize,
+    pub jitter: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            factor: 2.0,
+            min_delay_ms: 100,
+            max_delay_ms: 5000,
+            max_attempts: 5,
+            jitter: true,
+        }
+    }
+}
+
+impl From<&RetryConfig> for ExponentialBuilder {
+    fn from(config: &RetryConfig) -> Self {
+        let b = ExponentialBuilder::default()
+            .with_factor(config.factor)
+            .with_min_delay(Duration::from_millis(config.min_delay_ms))
+            .with_max_delay(Duration::from_millis(config.max_delay_ms))
+            .with_max_times(config.max_attempts);
+        if config.jitter {
+            b.with_jitter()
+        } else {
+            b
+        }
+    }
+}
@@ -5,7 +5,7 @@ use crate::{
 use chroma_log::config::LogConfig;
 use chroma_segment::local_segment_manager::LocalSegmentManagerConfig;
 use chroma_sqlite::config::SqliteDBConfig;
-use chroma_sysdb::SysDbConfig;
+use chroma_sysdb::{SysDbBackendConfig, SysDbConfig};
 use figment::providers::{Env, Format, Yaml};
 use mdac::CircuitBreakerConfig;
 use rust_embed::Embed;
@@ -18,7 +18,7 @@ pub struct ScorecardRule {
 }
 
 fn default_sysdb_config() -> SysDbConfig {
-    SysDbConfig::Sqlite(Default::default())
+    SysDbConfig::from(SysDbBackendConfig::Sqlite(Default::default()))
 }
 
 fn default_log_config() -> LogConfig {
@@ -189,9 +189,10 @@ mod tests {
     fn test_load_config() {
         let config = FrontendServerConfig::load();
         let sysdb_config = config.frontend.sysdb;
-        let sysdb_config = match sysdb_config {
-            chroma_sysdb::SysDbConfig::Grpc(grpc_sys_db_config) => grpc_sys_db_config,
-            chroma_sysdb::SysDbConfig::Sqlite(_) => {
+        assert!(sysdb_config.cache.is_none());
+        let sysdb_config = match sysdb_config.backend {
+            chroma_sysdb::SysDbBackendConfig::Grpc(grpc_sys_db_config) => grpc_sys_db_config,
+            chroma_sysdb::SysDbBackendConfig::Sqlite(_) => {
                 panic!("Expected grpc sysdb config, got sqlite sysdb config")
             }
         };
@@ -223,5 +224,30 @@ mod tests {
     fn single_node_full_config_valid() {
         let config = FrontendServerConfig::load_from_path("sample_configs/single_node_full.yaml");
         assert_eq!(config.port, 8000);
+        assert!(matches!(
+            config.frontend.sysdb.backend,
+            chroma_sysdb::SysDbBackendConfig::Sqlite(_)
+        ));
+        assert!(config.frontend.sysdb.cache.is_none());
+    }
+
+    #[test]
+    fn single_node_config_valid() {
+        let config = FrontendServerConfig::load_from_path("sample_configs/single_node.yaml");
+        assert_eq!(config.persist_path, "./chroma");
+        assert!(matches!(
+            config.frontend.sysdb.backend,
+            chroma_sysdb::SysDbBackendConfig::Sqlite(_)
+        ));
+    }
+
+    #[test]
+    fn docker_single_node_config_valid() {
+        let config = FrontendServerConfig::load_from_path("sample_configs/docker_single_node.yaml");
+        assert_eq!(config.persist_path, "/data");
+        assert!(matches!(
+            config.frontend.sysdb.backend,
+            chroma_sysdb::SysDbBackendConfig::Sqlite(_)
+        ));
     }
 }
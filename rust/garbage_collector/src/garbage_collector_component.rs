@@ -4,7 +4,7 @@ use async_trait::async_trait;
 use chroma_config::{registry::Registry, Configurable};
 use chroma_error::ChromaError;
 use chroma_storage::Storage;
-use chroma_sysdb::{SysDb, SysDbConfig};
+use chroma_sysdb::{SysDb, SysDbBackendConfig, SysDbConfig};
 use chroma_system::{
     Component, ComponentContext, ComponentHandle, Dispatcher, Handler, Orchestrator,
 };
@@ -173,7 +173,7 @@ impl Configurable<GarbageCollectorConfig> for GarbageCollector {
         config: &GarbageCollectorConfig,
         registry: &Registry,
     ) -> Result<Self, Box<dyn ChromaError>> {
-        let sysdb_config = SysDbConfig::Grpc(config.sysdb_config.clone());
+        let sysdb_config = SysDbConfig::from(SysDbBackendConfig::Grpc(config.sysdb_config.clone()));
         let sysdb_client = SysDb::try_from_config(&sysdb_config, registry).await?;
         let storage = Storage::try_from_config(&config.storage_config, registry).await?;
 
@@ -110,23 +110,47 @@ impl Operator<DeleteVersionsAtSysDbInput, DeleteVersionsAtSysDbOutput>
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chroma_sysdb::TestSysDb;
+    use chroma_sysdb::{FlushCompactionRequest, TestSysDb};
+    use chroma_types::Collection;
+    use std::sync::Arc;
 
     #[tokio::test]
     async fn test_delete_versions_success() {
-        let sysdb = SysDb::Test(TestSysDb::new());
+        // Flush the collection up to version 5 and mark 2 through 4 for deletion.
+        let mut test_sysdb = TestSysDb::new();
+        let collection = Collection::test_collection(3);
+        let collection_id = collection.collection_id;
+        let tenant = collection.tenant.clone();
+        test_sysdb.add_collection(collection);
+        for version in 0..5 {
+            test_sysdb
+                .flush(FlushCompactionRequest::new(
+                    tenant.clone(),
+                    collection_id,
+                    version as i64 + 1,
+                    version,
+                    Arc::new([]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
         let version_file = CollectionVersionFile::default();
         let versions_to_delete = VersionListForCollection {
-            collection_id: "test_collection".to_string(),
+            collection_id: collection_id.to_string(),
             database_id: "default".to_string(),
             tenant_id: "default".to_string(),
             versions: vec![2, 3, 4],
         };
+        test_sysdb
+            .mark_version_for_deletion(123, vec![versions_to_delete.clone()])
+            .await
+            .unwrap();
 
         let input = DeleteVersionsAtSysDbInput {
             version_file: version_file.clone(),
             versions_to_delete: versions_to_delete.clone(),
-            sysdb_client: sysdb,
+            sysdb_client: SysDb::Test(test_sysdb.clone()),
             epoch_id: 123,
             unused_s3_files: HashSet::new(),
         };
@@ -138,6 +162,14 @@ mod tests {
         let output = result.unwrap();
         assert_eq!(output.version_file, version_file);
         assert_eq!(output.versions_to_delete, versions_to_delete);
+        for version in [2, 3, 4] {
+            assert!(test_sysdb
+                .get_collection_at_version(collection_id, version)
+                .is_err());
+        }
+        assert!(test_sysdb
+            .get_collection_at_version(collection_id, 5)
+            .is_ok());
     }
 
     #[tokio::test]
@@ -73,14 +73,39 @@ impl Operator<MarkVersionsAtSysDbInput, MarkVersionsAtSysDbOutput> for MarkVersi
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chroma_sysdb::TestSysDb;
+    use chroma_sysdb::{FlushCompactionRequest, TestSysDb};
+    use chroma_types::Collection;
+    use std::sync::Arc;
+
+    // A test sysdb holding one collection flushed until it reaches `version`.
+    async fn sysdb_at_version(version: i32) -> (SysDb, String) {
+        let mut sysdb = TestSysDb::new();
+        let collection = Collection::test_collection(3);
+        let collection_id = collection.collection_id;
+        let tenant = collection.tenant.clone();
+        sysdb.add_collection(collection);
+        for current in 0..version {
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    tenant.clone(),
+                    collection_id,
+                    current as i64 + 1,
+                    current,
+                    Arc::new([]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+        (SysDb::Test(sysdb), collection_id.to_string())
+    }
 
     #[tokio::test]
     async fn test_mark_versions_success() {
-        let sysdb = SysDb::Test(TestSysDb::new());
+        let (sysdb, collection_id) = sysdb_at_version(5).await;
         let version_file = CollectionVersionFile::default();
         let versions_to_delete = VersionListForCollection {
-            collection_id: "test_collection".to_string(),
+            collection_id,
             database_id: "default".to_string(),
             tenant_id: "default".to_string(),
             versions: vec![2, 3, 4],
@@ -131,10 +156,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_mark_versions_error() {
-        let sysdb = SysDb::Test(TestSysDb::new());
+        // Version 1 is the live version, so it can't be marked.
+        let (sysdb, collection_id) = sysdb_at_version(1).await;
         let version_file = CollectionVersionFile::default();
         let versions_to_delete = VersionListForCollection {
-            collection_id: "test_collection".to_string(),
+            collection_id,
             database_id: "default".to_string(),
             tenant_id: "default".to_string(),
             versions: vec![1],
@@ -154,7 +180,7 @@ mod tests {
         assert!(result.is_err());
         match result {
             Err(MarkVersionsAtSysDbError::SysDBError(err)) => {
-                assert_eq!(err, "Failed to mark version for deletion");
+                assert_eq!(err, "Version 1 is the live version of its collection");
             }
             _ => panic!("Expected SysDBError"),
         }
@@ -6,7 +6,7 @@ mod tests {
         ObjectStoreBucketConfig, ObjectStoreConfig, ObjectStoreType, StorageConfig,
     };
     use chroma_storage::Storage;
-    use chroma_sysdb::{GrpcSysDbConfig, SysDb, SysDbConfig};
+    use chroma_sysdb::{GrpcSysDbConfig, SysDb, SysDbBackendConfig, SysDbConfig};
     use chroma_system::{Dispatcher, Orchestrator, System};
     use chroma_types::CollectionUuid;
     use garbage_collector_library::garbage_collector_orchestrator::GarbageCollectorOrchestrator;
@@ -203,13 +203,14 @@ mod tests {
                 println!("Initial records: {:?}", initial_records);
 
                 // Run GC multiple times
-                let sysdb_config = SysDbConfig::Grpc(GrpcSysDbConfig {
+                let sysdb_config = SysDbConfig::from(SysDbBackendConfig::Grpc(GrpcSysDbConfig {
                     host: "localhost".to_string(),
                     port: 50051,
                     connect_timeout_ms: 5000,
                     request_timeout_ms: 10000,
                     num_channels: 1,
-                });
+                    ..Default::default()
+                }));
 
                 let mut sysdb = SysDb::try_from_config(&sysdb_config, &registry).await.unwrap();
 
@@ -13,7 +13,7 @@ use chroma_frontend::{
 use chroma_log::config::{LogConfig, SqliteLogConfig};
 use chroma_segment::local_segment_manager::LocalSegmentManagerConfig;
 use chroma_sqlite::config::SqliteDBConfig;
-use chroma_sysdb::{SqliteSysDbConfig, SysDbConfig};
+use chroma_sysdb::{SqliteSysDbConfig, SysDbBackendConfig, SysDbConfig};
 use chroma_system::System;
 use chroma_types::{
     Collection, CollectionMetadataUpdate, CountCollectionsRequest, CountResponse,
@@ -94,10 +94,10 @@ impl Bindings {
         };
 
         // TODO: consume the log configuration from the input python
-        let sysdb_config = SysDbConfig::Sqlite(SqliteSysDbConfig {
+        let sysdb_config = SysDbConfig::from(SysDbBackendConfig::Sqlite(SqliteSysDbConfig {
             log_topic_namespace: "default".to_string(),
             log_tenant: "default".to_string(),
-        });
+        }));
 
         let log_config = LogConfig::Sqlite(SqliteLogConfig {
             tenant_id: "default".to_string(),
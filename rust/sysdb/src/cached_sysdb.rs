@@ -0,0 +1,332 @@
+use crate::{SysDb, SysDbCacheConfig};
+use chroma_cache::{Cache, CacheConfig, FoyerCacheConfig, Weighted};
+use chroma_error::ChromaError;
+use chroma_types::{
+    Collection, CollectionAndSegments, CollectionUuid, GetCollectionWithSegmentsError,
+    GetCollectionsError, GetSegmentsError, Segment, SegmentScope, SegmentUuid,
+};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+pub(crate) struct CacheEntry<T> {
+    value: T,
+    fetched_at: Instant,
+}
+
+impl<T> Weighted for CacheEntry<T> {
+    fn weight(&self) -> usize {
+        1
+    }
+}
+
+type EntryCache<T> = Arc<dyn Cache<CollectionUuid, CacheEntry<T>>>;
+
+/// Wraps any [`SysDb`] with a read-through cache for the collection and
+/// segment lookups query orchestrators make on every request.
+///
+/// Only `get_collections` by id, `get_segments` for a whole collection and
+/// `get_collection_with_segments` are cached; every other call goes straight
+/// to the wrapped sysdb. The collection-with-segments view is fetched and
+/// cached as one entry so its collection and segments always come from the
+/// same read. Entries expire
+/// after the configured TTL and are dropped as soon as a write to the
+/// collection goes through this client, so writes made by other clients are
+/// picked up within one TTL.
+#[derive(Clone, Debug)]
+pub struct CachedSysDb {
+    pub(crate) inner: Box<SysDb>,
+    ttl: Duration,
+    collections: EntryCache<Vec<Collection>>,
+    segments: EntryCache<Vec<Segment>>,
+    collections_with_segments: EntryCache<CollectionAndSegments>,
+}
+
+impl CachedSysDb {
+    pub async fn new(
+        inner: SysDb,
+        config: &SysDbCacheConfig,
+    ) -> Result<Self, Box<dyn ChromaError>> {
+        let cache_config = CacheConfig::Memory(FoyerCacheConfig {
+            capacity: config.capacity,
+            ..Default::default()
+        });
+        Ok(CachedSysDb {
+            inner: Box::new(inner),
+            ttl: Duration::from_millis(config.ttl_ms),
+            collections: chroma_cache::from_config(&cache_config).await?.into(),
+            segments: chroma_cache::from_config(&cache_config).await?.into(),
+            collections_with_segments: chroma_cache::from_config(&cache_config).await?.into(),
+        })
+    }
+
+    async fn lookup<T>(&self, cache: &EntryCache<T>, collection_id: &CollectionUuid) -> Option<T>
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        match cache.get(collection_id).await {
+            Ok(Some(entry)) if entry.fetched_at.elapsed() < self.ttl => Some(entry.value),
+            Ok(Some(_)) => {
+                cache.remove(collection_id).await;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn get_collections(
+        &mut self,
+        collection_id: Option<CollectionUuid>,
+        name: Option<String>,
+        tenant: Option<String>,
+        database: Option<String>,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Collection>, GetCollectionsError> {
+        let cache_key = match (collection_id, &name, &tenant, &database, limit, offset) {
+            (Some(collection_id), None, None, None, None, 0) => Some(collection_id),
+            _ => None,
+        };
+        if let Some(collection_id) = cache_key {
+            if let Some(collections) = self.lookup(&self.collections, &collection_id).await {
+                return Ok(collections);
+            }
+        }
+
+        let collections = Box::pin(self.inner.get_collections(
+            collection_id,
+            name,
+            tenant,
+            database,
+            limit,
+            offset,
+        ))
+        .await?;
+        // Like the frontend's collection cache, skip collections that have no
+        // dimension yet so the first write is not hidden behind a stale entry.
+        if let Some(collection_id) = cache_key {
+            if !collections.is_empty() && collections.iter().all(|c| c.dimension.is_some()) {
+                self.collections
+                    .insert(
+                        collection_id,
+                        CacheEntry {
+                            value: collections.clone(),
+                            fetched_at: Instant::now(),
+                        },
+                    )
+                    .await;
+            }
+        }
+        Ok(collections)
+    }
+
+    pub async fn get_segments(
+        &mut self,
+        id: Option<SegmentUuid>,
+        r#type: Option<String>,
+        scope: Option<SegmentScope>,
+        collection: CollectionUuid,
+    ) -> Result<Vec<Segment>, GetSegmentsError> {
+        let cacheable = id.is_none() && r#type.is_none() && scope.is_none();
+        if cacheable {
+            if let Some(segments) = self.lookup(&self.segments, &collection).await {
+                return Ok(segments);
+            }
+        }
+
+        let segments = Box::pin(self.inner.get_segments(id, r#type, scope, collection)).await?;
+        if cacheable && !segments.is_empty() {
+            self.segments
+                .insert(
+                    collection,
+                    CacheEntry {
+                        value: segments.clone(),
+                        fetched_at: Instant::now(),
+                    },
+                )
+                .await;
+        }
+        Ok(segments)
+    }
+
+    pub async fn get_collection_with_segments(
+        &mut self,
+        collection_id: CollectionUuid,
+    ) -> Result<CollectionAndSegments, GetCollectionWithSegmentsError> {
+        if let Some(collection_and_segments) = self
+            .lookup(&self.collections_with_segments, &collection_id)
+            .await
+        {
+            return Ok(collection_and_segments);
+        }
+
+        let collection_and_segments =
+            Box::pin(self.inner.get_collection_with_segments(collection_id)).await?;
+        if collection_and_segments.collection.dimension.is_some() {
+            self.collections_with_segments
+                .insert(
+                    collection_id,
+                    CacheEntry {
+                        value: collection_and_segments.clone(),
+                        fetched_at: Instant::now(),
+                    },
+                )
+                .await;
+        }
+        Ok(collection_and_segments)
+    }
+
+    /// Drops any cached collection and segments for `collection_id`.
+    pub async fn invalidate(&self, collection_id: CollectionUuid) {
+        self.collections.remove(&collection_id).await;
+        self.segments.remove(&collection_id).await;
+        self.collections_with_segments.remove(&collection_id).await;
+    }
+
+    pub(crate) async fn clear(&self) {
+        // The in-memory caches never fail to clear.
+        let _ = self.collections.clear().await;
+        let _ = self.segments.clear().await;
+        let _ = self.collections_with_segments.clear().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TestSysDb;
+
+    async fn cached(test: &TestSysDb, ttl_ms: u64) -> SysDb {
+        let config = SysDbCacheConfig {
+            ttl_ms,
+            capacity: 100,
+        };
+        SysDb::Cached(
+            CachedSysDb::new(SysDb::Test(test.clone()), &config)
+                .await
+                .unwrap(),
+        )
+    }
+
+    async fn collection_name(sysdb: &mut SysDb, collection_id: CollectionUuid) -> String {
+        sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap()
+            .remove(0)
+            .name
+    }
+
+    #[tokio::test]
+    async fn test_cached_sysdb_invalidates_on_flush() {
+        let mut test = TestSysDb::new();
+        let collection = Collection::test_collection(3);
+        let collection_id = collection.collection_id;
+        let tenant = collection.tenant.clone();
+        test.add_collection(collection.clone());
+        let mut sysdb = cached(&test, 60_000).await;
+        assert_eq!(
+            collection_name(&mut sysdb, collection_id).await,
+            collection.name
+        );
+
+        // Writes that bypass the cache are not visible until it is invalidated.
+        test.update_collection(collection_id, Some("renamed".to_string()), None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            collection_name(&mut sysdb, collection_id).await,
+            collection.name
+        );
+
+        sysdb
+            .flush_compaction(tenant, collection_id, 1, 0, Arc::new([]), 0)
+            .await
+            .unwrap();
+        let collections = sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(collections[0].name, "renamed");
+        assert_eq!(collections[0].version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cached_sysdb_serves_collection_with_segments() {
+        let mut test = TestSysDb::new();
+        let collection_and_segments = CollectionAndSegments::test(3);
+        let collection_id = collection_and_segments.collection.collection_id;
+        test.add_collection(collection_and_segments.collection.clone());
+        test.add_segment(collection_and_segments.metadata_segment.clone());
+        test.add_segment(collection_and_segments.record_segment.clone());
+        test.add_segment(collection_and_segments.vector_segment.clone());
+        let mut sysdb = cached(&test, 60_000).await;
+        sysdb
+            .get_collection_with_segments(collection_id)
+            .await
+            .unwrap();
+
+        test.update_collection(collection_id, Some("renamed".to_string()), None, None)
+            .await
+            .unwrap();
+        let cached_view = sysdb
+            .get_collection_with_segments(collection_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            cached_view.collection.name,
+            collection_and_segments.collection.name
+        );
+        assert_eq!(
+            cached_view.record_segment.id,
+            collection_and_segments.record_segment.id
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cached_sysdb_collection_with_segments_is_one_read() {
+        let mut test = TestSysDb::new();
+        let collection_and_segments = CollectionAndSegments::test(3);
+        let collection_id = collection_and_segments.collection.collection_id;
+        let tenant = collection_and_segments.collection.tenant.clone();
+        test.add_collection(collection_and_segments.collection.clone());
+        test.add_segment(collection_and_segments.metadata_segment.clone());
+        test.add_segment(collection_and_segments.record_segment.clone());
+        test.add_segment(collection_and_segments.vector_segment.clone());
+        let mut sysdb = cached(&test, 60_000).await;
+        // Caching the collection on its own must not leak into the combined
+        // view once the collection moves on underneath the cache.
+        sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        test.flush_compaction(tenant, collection_id, 1, 0, Arc::new([]), 0)
+            .await
+            .unwrap();
+
+        let fresh = sysdb
+            .get_collection_with_segments(collection_id)
+            .await
+            .unwrap();
+        assert_eq!(fresh.collection.version, 1);
+        assert_eq!(
+            fresh.record_segment.id,
+            collection_and_segments.record_segment.id
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cached_sysdb_expires_entries() {
+        let mut test = TestSysDb::new();
+        let collection = Collection::test_collection(3);
+        let collection_id = collection.collection_id;
+        test.add_collection(collection);
+        let mut sysdb = cached(&test, 0).await;
+        collection_name(&mut sysdb, collection_id).await;
+
+        test.update_collection(collection_id, Some("renamed".to_string()), None, None)
+            .await
+            .unwrap();
+        assert_eq!(collection_name(&mut sysdb, collection_id).await, "renamed");
+    }
+}
@@ -1,7 +1,8 @@
-use crate::{sqlite::SqliteSysDb, GrpcSysDb, SysDb};
+use crate::{sqlite::SqliteSysDb, CachedSysDb, GrpcSysDb, SysDb};
 use async_trait::async_trait;
 use chroma_config::{
     registry::{Injectable, Registry},
+    retry::RetryConfig,
     Configurable,
 };
 use chroma_error::ChromaError;
@@ -21,6 +22,8 @@ pub struct GrpcSysDbConfig {
     pub request_timeout_ms: u64,
     #[serde(default = "GrpcSysDbConfig::default_num_channels")]
     pub num_channels: usize,
+    #[serde(default = "RetryConfig::default")]
+    pub retry: RetryConfig,
 }
 
 impl GrpcSysDbConfig {
@@ -53,6 +56,38 @@ impl Default for GrpcSysDbConfig {
             connect_timeout_ms: GrpcSysDbConfig::default_connect_timeout_ms(),
             request_timeout_ms: GrpcSysDbConfig::default_request_timeout_ms(),
             num_channels: GrpcSysDbConfig::default_num_channels(),
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+/// Client-side cache for collection and segment lookups.
+/// # Fields
+/// - `ttl_ms` - How long an entry is served before it is fetched again
+/// - `capacity` - The maximum number of entries per cache
+#[derive(Deserialize, Debug, Clone, Serialize)]
+pub struct SysDbCacheConfig {
+    #[serde(default = "SysDbCacheConfig::default_ttl_ms")]
+    pub ttl_ms: u64,
+    #[serde(default = "SysDbCacheConfig::default_capacity")]
+    pub capacity: usize,
+}
+
+impl SysDbCacheConfig {
+    fn default_ttl_ms() -> u64 {
+        5000
+    }
+
+    fn default_capacity() -> usize {
+        10000
+    }
+}
+
+impl Default for SysDbCacheConfig {
+    fn default() -> Self {
+        SysDbCacheConfig {
+            ttl_ms: SysDbCacheConfig::default_ttl_ms(),
+            capacity: SysDbCacheConfig::default_capacity(),
         }
     }
 }
@@ -77,16 +112,36 @@ impl Default for SqliteSysDbConfig {
 //////////////////////// SYSDB CONFIG ////////////////////////
 
 #[derive(Deserialize, Debug, Clone, Serialize)]
-pub enum SysDbConfig {
+pub enum SysDbBackendConfig {
     #[serde(alias = "grpc")]
     Grpc(GrpcSysDbConfig),
     #[serde(alias = "sqlite")]
     Sqlite(SqliteSysDbConfig),
 }
 
-impl Default for SysDbConfig {
+impl Default for SysDbBackendConfig {
     fn default() -> Self {
-        SysDbConfig::Grpc(GrpcSysDbConfig::default())
+        SysDbBackendConfig::Grpc(GrpcSysDbConfig::default())
+    }
+}
+
+/// The sysdb backend to connect to and, when `cache` is set, the client-side
+/// cache to wrap it in. The backend is flattened so existing `grpc:` and
+/// `sqlite:` sections keep working, with `cache:` as their sibling.
+#[derive(Deserialize, Debug, Clone, Serialize, Default)]
+pub struct SysDbConfig {
+    #[serde(flatten)]
+    pub backend: SysDbBackendConfig,
+    #[serde(default)]
+    pub cache: Option<SysDbCacheConfig>,
+}
+
+impl From<SysDbBackendConfig> for SysDbConfig {
+    fn from(backend: SysDbBackendConfig) -> Self {
+        SysDbConfig {
+            backend,
+            cache: None,
+        }
     }
 }
 
@@ -98,16 +153,113 @@ impl Configurable<SysDbConfig> for SysDb {
         config: &SysDbConfig,
         registry: &Registry,
     ) -> Result<Self, Box<dyn ChromaError>> {
-        let out = match &config {
-            SysDbConfig::Grpc(grpc_config) => {
+        let backend = match &config.backend {
+            SysDbBackendConfig::Grpc(grpc_config) => {
                 SysDb::Grpc(GrpcSysDb::try_from_config(grpc_config, registry).await?)
             }
-            SysDbConfig::Sqlite(sqlite_config) => {
+            SysDbBackendConfig::Sqlite(sqlite_config) => {
                 SysDb::Sqlite(SqliteSysDb::try_from_config(sqlite_config, registry).await?)
             }
         };
+        let out = match &config.cache {
+            Some(cache_config) => SysDb::Cached(CachedSysDb::new(backend, cache_config).await?),
+            None => backend,
+        };
 
         registry.register(out.clone());
         Ok(out)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use figment::providers::{Format, Yaml};
+
+    #[derive(Deserialize)]
+    struct Root {
+        sysdb: SysDbConfig,
+    }
+
+    fn parse(yaml: &str) -> SysDbConfig {
+        figment::Figment::from(Yaml::string(yaml))
+            .extract::<Root>()
+            .expect("sysdb config should deserialize")
+            .sysdb
+    }
+
+    #[test]
+    fn test_grpc_without_cache() {
+        let config = parse(
+            r#"
+            sysdb:
+                grpc:
+                    host: "localhost"
+                    port: 50052
+                    connect_timeout_ms: 1000
+                    request_timeout_ms: 2000
+            "#,
+        );
+        let SysDbBackendConfig::Grpc(grpc) = config.backend else {
+            panic!("Expected grpc sysdb config");
+        };
+        assert_eq!(grpc.host, "localhost");
+        assert_eq!(grpc.port, 50052);
+        assert_eq!(grpc.connect_timeout_ms, 1000);
+        assert_eq!(grpc.request_timeout_ms, 2000);
+        assert_eq!(grpc.num_channels, 5);
+        assert!(config.cache.is_none());
+    }
+
+    #[test]
+    fn test_grpc_with_cache() {
+        let config = parse(
+            r#"
+            sysdb:
+                grpc:
+                    host: "localhost"
+                    port: 50052
+                cache:
+                    ttl_ms: 250
+                    capacity: 64
+            "#,
+        );
+        let SysDbBackendConfig::Grpc(grpc) = config.backend else {
+            panic!("Expected grpc sysdb config");
+        };
+        assert_eq!(grpc.host, "localhost");
+        assert_eq!(grpc.port, 50052);
+        let cache = config.cache.expect("Expected a cache config");
+        assert_eq!(cache.ttl_ms, 250);
+        assert_eq!(cache.capacity, 64);
+    }
+
+    #[test]
+    fn test_cache_defaults() {
+        let config = parse(
+            r#"
+            sysdb:
+                grpc: {}
+                cache: {}
+            "#,
+        );
+        assert!(matches!(config.backend, SysDbBackendConfig::Grpc(_)));
+        let cache = config.cache.expect("Expected a cache config");
+        assert_eq!(cache.ttl_ms, 5000);
+        assert_eq!(cache.capacity, 10000);
+    }
+
+    #[test]
+    fn test_sqlite_without_cache() {
+        let config = parse(
+            r#"
+            sysdb:
+                sqlite:
+                    log_topic_namespace: "default"
+                    log_tenant: "default"
+            "#,
+        );
+        assert!(matches!(config.backend, SysDbBackendConfig::Sqlite(_)));
+        assert!(config.cache.is_none());
+    }
+}
@@ -1,8 +1,10 @@
+pub mod cached_sysdb;
 pub mod config;
 pub mod sqlite;
 #[allow(clippy::module_inception)]
 pub mod sysdb;
 pub mod test_sysdb;
+pub use cached_sysdb::*;
 pub use config::*;
 pub use sysdb::*;
 pub use test_sysdb::*;
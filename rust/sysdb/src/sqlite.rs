@@ -350,7 +350,7 @@ impl SqliteSysDb {
         name: Option<String>,
         metadata: Option<CollectionMetadataUpdate>,
         dimension: Option<u32>,
-    ) -> Result<(), UpdateCollectionError> {
+    ) -> Result<Collection, UpdateCollectionError> {
         let mut tx = self
             .db
             .get_conn()
@@ -400,11 +400,29 @@ impl SqliteSysDb {
             }
         }
 
+        // Read back inside the transaction so a missing collection rolls back
+        // a metadata-only update instead of committing orphaned rows.
+        let collection = self
+            .get_collections_with_conn(
+                &mut *tx,
+                Some(vec![collection_id]),
+                None,
+                None,
+                None,
+                None,
+                0,
+            )
+            .await
+            .map_err(|e| UpdateCollectionError::Internal(e.boxed()))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| UpdateCollectionError::NotFound(collection_id.to_string()))?;
+
         tx.commit()
             .await
             .map_err(|e| UpdateCollectionError::Internal(e.into()))?;
 
-        Ok(())
+        Ok(collection)
     }
 
     async fn create_segment_with_tx<C>(
@@ -452,7 +470,7 @@ impl SqliteSysDb {
     ) -> Result<Vec<Collection>, GetCollectionsError> {
         self.get_collections_with_conn(
             self.db.get_conn(),
-            collection_id,
+            collection_id.map(|collection_id| vec![collection_id]),
             name,
             tenant,
             database,
@@ -462,6 +480,24 @@ impl SqliteSysDb {
         .await
     }
 
+    /// The collections among `collection_ids`, read with one query. Unknown ids
+    /// are left out.
+    pub(crate) async fn get_collections_by_ids(
+        &self,
+        collection_ids: Vec<CollectionUuid>,
+    ) -> Result<Vec<Collection>, GetCollectionsError> {
+        self.get_collections_with_conn(
+            self.db.get_conn(),
+            Some(collection_ids),
+            None,
+            None,
+            None,
+            None,
+            0,
+        )
+        .await
+    }
+
     pub(crate) async fn delete_collection(
         &self,
         tenant: String,
@@ -509,7 +545,7 @@ impl SqliteSysDb {
         let collections = self
             .get_collections_with_conn(
                 self.db.get_conn(),
-                Some(collection_id),
+                Some(vec![collection_id]),
                 None,
                 None,
                 None,
@@ -559,7 +595,7 @@ impl SqliteSysDb {
     async fn get_collections_with_conn<'a, C>(
         &self,
         conn: C,
-        collection_id: Option<CollectionUuid>,
+        collection_ids: Option<Vec<CollectionUuid>>,
         name: Option<String>,
         tenant: Option<String>,
         database: Option<String>,
@@ -597,9 +633,9 @@ impl SqliteSysDb {
                             sea_query::Expr::col(table::Databases::TenantId).eq(tenant)
                         }),
                     )
-                    .add_option(collection_id.map(|collection_id| {
+                    .add_option(collection_ids.map(|collection_ids| {
                         sea_query::Expr::col((table::Collections::Table, table::Collections::Id))
-                            .eq(collection_id.to_string())
+                            .is_in(collection_ids.iter().map(|id| id.to_string()))
                     })),
             )
             .order_by(
@@ -1237,7 +1273,7 @@ mod tests {
             UpdateMetadataValue::Str("value1".to_string()),
         );
 
-        sysdb
+        let updated = sysdb
             .update_collection(
                 collection_id,
                 Some("new_name".to_string()),
@@ -1246,6 +1282,8 @@ mod tests {
             )
             .await
             .unwrap();
+        assert_eq!(updated.name, "new_name");
+        assert_eq!(updated.dimension, Some(1024));
 
         let collections = sysdb
             .get_collections(Some(collection_id), None, None, None, None, 0)
@@ -1314,6 +1352,49 @@ mod tests {
         assert_eq!(result.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_get_collections_by_ids() {
+        let db = get_new_sqlite_db().await;
+        let sysdb = SqliteSysDb::new(db, "default".to_string(), "default".to_string());
+
+        let mut collection_ids = Vec::new();
+        for name in ["a", "b", "c"] {
+            let collection_id = CollectionUuid::new();
+            sysdb
+                .create_collection(
+                    "default_tenant".to_string(),
+                    "default_database".to_string(),
+                    collection_id,
+                    name.to_string(),
+                    vec![],
+                    serde_json::Value::Null,
+                    None,
+                    None,
+                    false,
+                )
+                .await
+                .unwrap();
+            collection_ids.push(collection_id);
+        }
+
+        let collections = sysdb
+            .get_collections_by_ids(vec![
+                collection_ids[2],
+                CollectionUuid::new(),
+                collection_ids[0],
+            ])
+            .await
+            .unwrap();
+        let mut found = collections
+            .iter()
+            .map(|collection| collection.collection_id)
+            .collect::<Vec<_>>();
+        found.sort();
+        let mut expected = vec![collection_ids[0], collection_ids[2]];
+        expected.sort();
+        assert_eq!(found, expected);
+    }
+
     #[tokio::test]
     async fn test_get_collection_with_segments() {
         let db = get_new_sqlite_db().await;
@@ -1,7 +1,9 @@
 use super::test_sysdb::TestSysDb;
+use crate::cached_sysdb::CachedSysDb;
 use crate::sqlite::SqliteSysDb;
 use crate::GrpcSysDbConfig;
 use async_trait::async_trait;
+use backon::{ExponentialBuilder, Retryable};
 use chroma_config::registry::Registry;
 use chroma_config::Configurable;
 use chroma_error::{ChromaError, ErrorCodes, TonicError, TonicMissingFieldError};
@@ -21,8 +23,10 @@ use chroma_types::{
     Collection, CollectionConversionError, CollectionUuid, FlushCompactionResponse,
     FlushCompactionResponseConversionError, Segment, SegmentConversionError, SegmentScope, Tenant,
 };
+use opentelemetry::KeyValue;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
@@ -37,6 +41,7 @@ pub enum SysDb {
     Sqlite(SqliteSysDb),
     #[allow(dead_code)]
     Test(TestSysDb),
+    Cached(CachedSysDb),
 }
 
 impl SysDb {
@@ -47,7 +52,8 @@ impl SysDb {
         match self {
             SysDb::Grpc(grpc) => grpc.create_tenant(tenant_name).await,
             SysDb::Sqlite(sqlite) => sqlite.create_tenant(tenant_name).await,
-            SysDb::Test(_) => todo!(),
+            SysDb::Test(test) => test.create_tenant(tenant_name).await,
+            SysDb::Cached(cached) => Box::pin(cached.inner.create_tenant(tenant_name)).await,
         }
     }
 
@@ -58,7 +64,8 @@ impl SysDb {
         match self {
             SysDb::Grpc(grpc) => grpc.get_tenant(tenant_name).await,
             SysDb::Sqlite(sqlite) => sqlite.get_tenant(&tenant_name).await,
-            SysDb::Test(_) => todo!(),
+            SysDb::Test(test) => test.get_tenant(tenant_name).await,
+            SysDb::Cached(cached) => Box::pin(cached.inner.get_tenant(tenant_name)).await,
         }
     }
 
@@ -78,8 +85,17 @@ impl SysDb {
                     .create_database(database_id, &database_name, &tenant)
                     .await
             }
-            SysDb::Test(_) => {
-                todo!()
+            SysDb::Test(test) => {
+                test.create_database(database_id, database_name, tenant)
+                    .await
+            }
+            SysDb::Cached(cached) => {
+                Box::pin(
+                    cached
+                        .inner
+                        .create_database(database_id, database_name, tenant),
+                )
+                .await
             }
         }
     }
@@ -94,6 +110,9 @@ impl SysDb {
             SysDb::Grpc(grpc) => grpc.list_databases(tenant_id, limit, offset).await,
             SysDb::Sqlite(sqlite) => sqlite.list_databases(tenant_id, limit, offset).await,
             SysDb::Test(test) => test.list_databases(tenant_id, limit, offset).await,
+            SysDb::Cached(cached) => {
+                Box::pin(cached.inner.list_databases(tenant_id, limit, offset)).await
+            }
         }
     }
 
@@ -105,7 +124,10 @@ impl SysDb {
         match self {
             SysDb::Grpc(grpc) => grpc.get_database(database_name, tenant).await,
             SysDb::Sqlite(sqlite) => sqlite.get_database(&database_name, &tenant).await,
-            SysDb::Test(_) => todo!(),
+            SysDb::Test(test) => test.get_database(database_name, tenant).await,
+            SysDb::Cached(cached) => {
+                Box::pin(cached.inner.get_database(database_name, tenant)).await
+            }
         }
     }
 
@@ -117,7 +139,10 @@ impl SysDb {
         match self {
             SysDb::Grpc(grpc) => grpc.delete_database(database_name, tenant).await,
             SysDb::Sqlite(sqlite) => sqlite.delete_database(database_name, tenant).await,
-            SysDb::Test(_) => todo!(),
+            SysDb::Test(test) => test.delete_database(database_name, tenant).await,
+            SysDb::Cached(cached) => {
+                Box::pin(cached.inner.delete_database(database_name, tenant)).await
+            }
         }
     }
 
@@ -141,18 +166,51 @@ impl SysDb {
                     .await
             }
             SysDb::Test(test) => {
-                test.get_collections(collection_id, name, tenant, database)
+                test.get_collections(collection_id, name, tenant, database, limit, offset)
+                    .await
+            }
+            SysDb::Cached(cached) => {
+                cached
+                    .get_collections(collection_id, name, tenant, database, limit, offset)
                     .await
             }
         }
     }
 
+    /// Looks up `collection_ids` in input order, with `None` for ids that are
+    /// not found. Each backend answers with a single request.
+    pub async fn get_collections_by_ids(
+        &mut self,
+        collection_ids: Vec<CollectionUuid>,
+    ) -> Result<Vec<Option<Collection>>, GetCollectionsError> {
+        let found = match self {
+            SysDb::Grpc(grpc) => grpc.get_collections_by_ids(collection_ids.clone()).await?,
+            SysDb::Sqlite(sqlite) => {
+                sqlite
+                    .get_collections_by_ids(collection_ids.clone())
+                    .await?
+            }
+            SysDb::Test(test) => test.get_collections_by_ids(collection_ids.clone()).await?,
+            SysDb::Cached(cached) => {
+                return Box::pin(cached.inner.get_collections_by_ids(collection_ids)).await
+            }
+        };
+        let found = found
+            .into_iter()
+            .map(|collection| (collection.collection_id, collection))
+            .collect::<HashMap<_, _>>();
+        Ok(collection_ids
+            .iter()
+            .map(|collection_id| found.get(collection_id).cloned())
+            .collect())
+    }
+
     pub async fn count_collections(
         &mut self,
         tenant: String,
         database: Option<String>,
     ) -> Result<usize, CountCollectionsError> {
-        // TODO(Sanket): optimize sqlite and test implementation.
+        // TODO(Sanket): optimize sqlite implementation.
         match self {
             SysDb::Grpc(grpc) => grpc.count_collections(tenant, database).await,
             SysDb::Sqlite(sqlite) => Ok(sqlite
@@ -160,11 +218,10 @@ impl SysDb {
                 .await
                 .map_err(|_| CountCollectionsError::Internal)?
                 .len()),
-            SysDb::Test(test) => Ok(test
-                .get_collections(None, None, Some(tenant), database)
-                .await
-                .map_err(|_| CountCollectionsError::Internal)?
-                .len()),
+            SysDb::Test(test) => test.count_collections(Some(tenant), database).await,
+            SysDb::Cached(cached) => {
+                Box::pin(cached.inner.count_collections(tenant, database)).await
+            }
         }
     }
 
@@ -174,8 +231,13 @@ impl SysDb {
     ) -> Result<usize, GetCollectionSizeError> {
         match self {
             SysDb::Grpc(grpc) => grpc.get_collection_size(collection_id).await,
-            SysDb::Sqlite(_) => unimplemented!(),
+            SysDb::Sqlite(_) => Err(GetCollectionSizeError::Internal(
+                UnsupportedOperationError("get_collection_size").boxed(),
+            )),
             SysDb::Test(test) => test.get_collection_size(collection_id).await,
+            SysDb::Cached(cached) => {
+                Box::pin(cached.inner.get_collection_size(collection_id)).await
+            }
         }
     }
 
@@ -245,11 +307,24 @@ impl SysDb {
                     last_compaction_time_secs: 0,
                 };
 
-                test_sysdb.add_collection(collection.clone());
-                for seg in segments {
-                    test_sysdb.add_segment(seg);
-                }
-                Ok(collection)
+                test_sysdb
+                    .create_collection(collection, segments, get_or_create)
+                    .await
+            }
+            SysDb::Cached(cached) => {
+                let res = Box::pin(cached.inner.create_collection(
+                    tenant,
+                    database,
+                    collection_id,
+                    name,
+                    segments,
+                    metadata,
+                    dimension,
+                    get_or_create,
+                ))
+                .await;
+                cached.invalidate(collection_id).await;
+                res
             }
         }
     }
@@ -260,7 +335,7 @@ impl SysDb {
         name: Option<String>,
         metadata: Option<CollectionMetadataUpdate>,
         dimension: Option<u32>,
-    ) -> Result<(), UpdateCollectionError> {
+    ) -> Result<Collection, UpdateCollectionError> {
         match self {
             SysDb::Grpc(grpc) => {
                 grpc.update_collection(collection_id, name, metadata, dimension)
@@ -271,8 +346,20 @@ impl SysDb {
                     .update_collection(collection_id, name, metadata, dimension)
                     .await
             }
-            SysDb::Test(_) => {
-                todo!()
+            SysDb::Test(test) => {
+                test.update_collection(collection_id, name, metadata, dimension)
+                    .await
+            }
+            SysDb::Cached(cached) => {
+                let res = Box::pin(cached.inner.update_collection(
+                    collection_id,
+                    name,
+                    metadata,
+                    dimension,
+                ))
+                .await;
+                cached.invalidate(collection_id).await;
+                res
             }
         }
     }
@@ -294,9 +381,76 @@ impl SysDb {
                     .delete_collection(tenant, database, collection_id, segment_ids)
                     .await
             }
-            SysDb::Test(_) => {
-                todo!()
+            SysDb::Test(test) => test.delete_collection(collection_id).await,
+            SysDb::Cached(cached) => {
+                let res = Box::pin(cached.inner.delete_collection(
+                    tenant,
+                    database,
+                    collection_id,
+                    segment_ids,
+                ))
+                .await;
+                cached.invalidate(collection_id).await;
+                res
+            }
+        }
+    }
+
+    /// Brings back a soft-deleted collection.
+    ///
+    /// Only the test sysdb keeps soft-deleted collections around. The
+    /// coordinator's RestoreCollection restores a collection version, not a
+    /// deleted collection, so the other backends return an unsupported error.
+    pub async fn undelete_collection(
+        &mut self,
+        collection_id: CollectionUuid,
+    ) -> Result<(), DeleteCollectionError> {
+        match self {
+            SysDb::Test(test) => test.undelete_collection(collection_id),
+            SysDb::Cached(cached) => {
+                let res = Box::pin(cached.inner.undelete_collection(collection_id)).await;
+                cached.invalidate(collection_id).await;
+                res
+            }
+            SysDb::Grpc(_) | SysDb::Sqlite(_) => Err(DeleteCollectionError::Internal(
+                UnsupportedOperationError("undelete_collection").boxed(),
+            )),
+        }
+    }
+
+    /// Collections soft-deleted before `cutoff_time`. Only supported by the
+    /// test sysdb.
+    pub async fn list_soft_deleted_collections(
+        &mut self,
+        cutoff_time: i64,
+    ) -> Result<Vec<Collection>, GetCollectionsError> {
+        match self {
+            SysDb::Test(test) => Ok(test.list_soft_deleted_collections(cutoff_time)),
+            SysDb::Cached(cached) => {
+                Box::pin(cached.inner.list_soft_deleted_collections(cutoff_time)).await
+            }
+            SysDb::Grpc(_) | SysDb::Sqlite(_) => Err(GetCollectionsError::Internal(
+                UnsupportedOperationError("list_soft_deleted_collections").boxed(),
+            )),
+        }
+    }
+
+    /// Removes a soft-deleted collection for good. Only supported by the test
+    /// sysdb.
+    pub async fn purge_collection(
+        &mut self,
+        collection_id: CollectionUuid,
+    ) -> Result<(), DeleteCollectionError> {
+        match self {
+            SysDb::Test(test) => test.purge_collection(collection_id),
+            SysDb::Cached(cached) => {
+                let res = Box::pin(cached.inner.purge_collection(collection_id)).await;
+                cached.invalidate(collection_id).await;
+                res
             }
+            SysDb::Grpc(_) | SysDb::Sqlite(_) => Err(DeleteCollectionError::Internal(
+                UnsupportedOperationError("purge_collection").boxed(),
+            )),
         }
     }
 
@@ -305,8 +459,9 @@ impl SysDb {
     ) -> Result<Vec<CollectionToGcInfo>, GetCollectionsToGcError> {
         match self {
             SysDb::Grpc(grpc) => grpc.get_collections_to_gc().await,
-            SysDb::Sqlite(_) => todo!(),
-            SysDb::Test(_) => todo!(),
+            SysDb::Sqlite(_) => Err(GetCollectionsToGcError::Unsupported),
+            SysDb::Test(test) => test.list_collections_to_gc(i64::MAX, None).await,
+            SysDb::Cached(cached) => Box::pin(cached.inner.get_collections_to_gc()).await,
         }
     }
 
@@ -321,6 +476,7 @@ impl SysDb {
             SysDb::Grpc(grpc) => grpc.get_segments(id, r#type, scope, collection).await,
             SysDb::Sqlite(sqlite) => sqlite.get_segments(id, r#type, scope, collection).await,
             SysDb::Test(test) => test.get_segments(id, r#type, scope, collection).await,
+            SysDb::Cached(cached) => cached.get_segments(id, r#type, scope, collection).await,
         }
     }
 
@@ -335,7 +491,42 @@ impl SysDb {
                     .await
             }
             SysDb::Sqlite(sqlite) => sqlite.get_collection_with_segments(collection_id).await,
-            SysDb::Test(_test_sys_db) => todo!(),
+            SysDb::Test(test) => {
+                let (collection, segments) = test
+                    .get_collection_with_segments(collection_id)
+                    .map_err(|_| {
+                        GetCollectionWithSegmentsError::NotFound(collection_id.to_string())
+                    })?;
+                collection_and_segments(collection, &segments)
+            }
+            SysDb::Cached(cached) => cached.get_collection_with_segments(collection_id).await,
+        }
+    }
+
+    pub async fn list_collection_versions(
+        &mut self,
+        collection_id: CollectionUuid,
+        tenant_id: String,
+    ) -> Result<Vec<chroma_proto::CollectionVersionInfo>, ListCollectionVersionsError> {
+        match self {
+            SysDb::Grpc(grpc) => {
+                grpc.list_collection_versions(collection_id, tenant_id)
+                    .await
+            }
+            SysDb::Sqlite(_) => Err(ListCollectionVersionsError::Unsupported),
+            SysDb::Test(test) => Ok(test
+                .get_collection_versions(collection_id)
+                .into_iter()
+                .map(Into::into)
+                .collect()),
+            SysDb::Cached(cached) => {
+                Box::pin(
+                    cached
+                        .inner
+                        .list_collection_versions(collection_id, tenant_id),
+                )
+                .await
+            }
         }
     }
 
@@ -345,8 +536,37 @@ impl SysDb {
     ) -> Result<Vec<Tenant>, GetLastCompactionTimeError> {
         match self {
             SysDb::Grpc(grpc) => grpc.get_last_compaction_time(tanant_ids).await,
-            SysDb::Sqlite(_) => todo!(),
+            SysDb::Sqlite(_) => Err(GetLastCompactionTimeError::Unsupported),
             SysDb::Test(test) => test.get_last_compaction_time(tanant_ids).await,
+            SysDb::Cached(cached) => {
+                Box::pin(cached.inner.get_last_compaction_time(tanant_ids)).await
+            }
+        }
+    }
+
+    pub async fn set_last_compaction_time(
+        &mut self,
+        tenant_id: String,
+        last_compaction_time: i64,
+    ) -> Result<(), SetLastCompactionTimeError> {
+        match self {
+            SysDb::Grpc(grpc) => {
+                grpc.set_last_compaction_time(tenant_id, last_compaction_time)
+                    .await
+            }
+            SysDb::Sqlite(_) => Err(SetLastCompactionTimeError::Unsupported),
+            SysDb::Test(test) => {
+                test.set_last_compaction_time(tenant_id, last_compaction_time)
+                    .await
+            }
+            SysDb::Cached(cached) => {
+                Box::pin(
+                    cached
+                        .inner
+                        .set_last_compaction_time(tenant_id, last_compaction_time),
+                )
+                .await
+            }
         }
     }
 
@@ -371,7 +591,7 @@ impl SysDb {
                 )
                 .await
             }
-            SysDb::Sqlite(_) => todo!(),
+            SysDb::Sqlite(_) => Err(FlushCompactionError::Unsupported),
             SysDb::Test(test) => {
                 test.flush_compaction(
                     tenant_id,
@@ -383,6 +603,21 @@ impl SysDb {
                 )
                 .await
             }
+            SysDb::Cached(cached) => {
+                let res = Box::pin(cached.inner.flush_compaction(
+                    tenant_id,
+                    collection_id,
+                    log_position,
+                    collection_version,
+                    segment_flush_info,
+                    total_records_post_compaction,
+                ))
+                .await;
+                // Invalidate even on failure, a flush may have partially applied
+                // before the error surfaced.
+                cached.invalidate(collection_id).await;
+                res
+            }
         }
     }
 
@@ -397,9 +632,6 @@ impl SysDb {
                 let versions_clone = versions.clone();
                 test.mark_version_for_deletion(epoch_id, versions_clone)
                     .await
-                    .map_err(|e| {
-                        MarkVersionForDeletionError::FailedToMarkVersion(tonic::Status::internal(e))
-                    })
                     .map(|_| {
                         let mut result = HashMap::new();
                         for version in versions {
@@ -408,7 +640,10 @@ impl SysDb {
                         result
                     })
             }
-            SysDb::Sqlite(_) => todo!(),
+            SysDb::Cached(cached) => {
+                Box::pin(cached.inner.mark_version_for_deletion(epoch_id, versions)).await
+            }
+            SysDb::Sqlite(_) => Err(MarkVersionForDeletionError::Unsupported),
         }
     }
 
@@ -422,7 +657,10 @@ impl SysDb {
                 Ok(response)
             }
             SysDb::Test(client) => Ok(client.delete_collection_version(versions).await),
-            SysDb::Sqlite(_) => todo!(),
+            SysDb::Cached(cached) => {
+                Box::pin(cached.inner.delete_collection_version(versions)).await
+            }
+            SysDb::Sqlite(_) => Err(DeleteCollectionVersionError::Unsupported),
         }
     }
 
@@ -430,17 +668,64 @@ impl SysDb {
         match self {
             SysDb::Grpc(grpc) => grpc.reset().await,
             SysDb::Sqlite(sqlite) => sqlite.reset().await,
-            SysDb::Test(_) => todo!(),
+            SysDb::Test(test) => {
+                test.reset();
+                Ok(ResetResponse {})
+            }
+            SysDb::Cached(cached) => {
+                let res = Box::pin(cached.inner.reset()).await;
+                cached.clear().await;
+                res
+            }
         }
     }
 }
 
+/// Picks the metadata, record and vector segments of `collection` out of
+/// `segments`, for sysdbs that return them as a flat list.
+pub(crate) fn collection_and_segments(
+    collection: Collection,
+    segments: &[Segment],
+) -> Result<CollectionAndSegments, GetCollectionWithSegmentsError> {
+    let segment = |scope: SegmentScope| {
+        segments
+            .iter()
+            .find(|segment| segment.scope == scope)
+            .cloned()
+            .ok_or_else(|| {
+                GetCollectionWithSegmentsError::Field(format!("Missing {scope:?} segment"))
+            })
+    };
+    Ok(CollectionAndSegments {
+        metadata_segment: segment(SegmentScope::METADATA)?,
+        record_segment: segment(SegmentScope::RECORD)?,
+        vector_segment: segment(SegmentScope::VECTOR)?,
+        collection,
+    })
+}
+
 #[derive(Clone, Debug)]
 // Since this uses tonic transport channel, cloning is cheap. Each client only supports
 // one inflight request at a time, so we need to clone the client for each requester.
 pub struct GrpcSysDb {
-    #[allow(clippy::type_complexity)]
-    client: SysDbClient<chroma_tracing::GrpcTraceService<tonic::transport::Channel>>,
+    client: GrpcSysDbClient,
+    backoff: ExponentialBuilder,
+    retries: opentelemetry::metrics::Counter<u64>,
+}
+
+type GrpcSysDbClient = SysDbClient<chroma_tracing::GrpcTraceService<tonic::transport::Channel>>;
+
+// Messages of the coordinator errors in go/pkg/common/errors.go that the
+// client tells apart. Coordinators older than the status codes below send both
+// as Internal, so the message is still checked.
+pub(crate) const COORDINATOR_TENANT_NOT_FOUND: &str = "tenant not found";
+pub(crate) const COORDINATOR_COLLECTION_VERSION_STALE: &str = "collection version stale";
+
+fn is_retryable_error(e: &tonic::Status) -> bool {
+    e.code() == Code::Unavailable
+        || e.code() == Code::DeadlineExceeded
+        || e.code() == Code::Aborted
+        || e.code() == Code::ResourceExhausted
 }
 
 #[derive(Error, Debug)]
@@ -482,7 +767,12 @@ impl Configurable<GrpcSysDbConfig> for GrpcSysDb {
             .layer(chroma_tracing::GrpcTraceLayer)
             .service(channel);
         let client = SysDbClient::new(channel);
-        Ok(GrpcSysDb { client })
+        let meter = opentelemetry::global::meter("chroma");
+        Ok(GrpcSysDb {
+            client,
+            backoff: (&my_config.retry).into(),
+            retries: meter.u64_counter("sysdb_retries").build(),
+        })
     }
 }
 
@@ -500,6 +790,8 @@ pub enum GetCollectionsToGcError {
     ParsingError(#[from] Error),
     #[error("Grpc request failed")]
     RequestFailed(#[from] tonic::Status),
+    #[error("Garbage collection is not supported by this sysdb")]
+    Unsupported,
 }
 
 impl ChromaError for GetCollectionsToGcError {
@@ -507,6 +799,7 @@ impl ChromaError for GetCollectionsToGcError {
         match self {
             GetCollectionsToGcError::ParsingError(_) => ErrorCodes::Internal,
             GetCollectionsToGcError::RequestFailed(_) => ErrorCodes::Internal,
+            GetCollectionsToGcError::Unsupported => ErrorCodes::Unimplemented,
         }
     }
 }
@@ -530,6 +823,28 @@ impl TryFrom<chroma_proto::CollectionToGcInfo> for CollectionToGcInfo {
 }
 
 impl GrpcSysDb {
+    /// Sends an idempotent request on a fresh client per attempt, retrying
+    /// transient failures with backoff. Only use this for requests that are
+    /// safe to repeat.
+    async fn with_retry<T, F, Fut>(
+        &self,
+        method: &'static str,
+        mut request: F,
+    ) -> Result<T, tonic::Status>
+    where
+        F: FnMut(GrpcSysDbClient) -> Fut,
+        Fut: Future<Output = Result<T, tonic::Status>>,
+    {
+        (|| request(self.client.clone()))
+            .retry(self.backoff)
+            .when(is_retryable_error)
+            .notify(|e, delay| {
+                tracing::warn!("Retrying sysdb {} in {:?}: {}", method, delay, e);
+                self.retries.add(1, &[KeyValue::new("method", method)]);
+            })
+            .await
+    }
+
     pub async fn create_tenant(
         &mut self,
         tenant_name: String,
@@ -553,7 +868,13 @@ impl GrpcSysDb {
         let req = chroma_proto::GetTenantRequest {
             name: tenant_name.clone(),
         };
-        match self.client.get_tenant(req).await {
+        let res = self
+            .with_retry("get_tenant", |mut client| {
+                let req = req.clone();
+                async move { client.get_tenant(req).await }
+            })
+            .await;
+        match res {
             Ok(resp) => Ok(GetTenantResponse {
                 name: resp
                     .into_inner()
@@ -601,7 +922,13 @@ impl GrpcSysDb {
             limit: limit.map(|l| l as i32),
             offset: Some(offset as i32),
         };
-        match self.client.list_databases(req).await {
+        let res = self
+            .with_retry("list_databases", |mut client| {
+                let req = req.clone();
+                async move { client.list_databases(req).await }
+            })
+            .await;
+        match res {
             Ok(resp) => resp
                 .into_inner()
                 .databases
@@ -629,7 +956,12 @@ impl GrpcSysDb {
             name: database_name.clone(),
             tenant,
         };
-        let res = self.client.get_database(req).await;
+        let res = self
+            .with_retry("get_database", |mut client| {
+                let req = req.clone();
+                async move { client.get_database(req).await }
+            })
+            .await;
         match res {
             Ok(res) => {
                 let res = match res.into_inner().database {
@@ -686,15 +1018,18 @@ impl GrpcSysDb {
     ) -> Result<Vec<Collection>, GetCollectionsError> {
         // TODO: move off of status into our own error type
         let collection_id_str = collection_id.map(|id| String::from(id.0));
+        let req = chroma_proto::GetCollectionsRequest {
+            id: collection_id_str,
+            name,
+            limit: limit.map(|l| l as i32),
+            offset: Some(offset as i32),
+            tenant: tenant.unwrap_or("".to_string()),
+            database: database.unwrap_or("".to_string()),
+        };
         let res = self
-            .client
-            .get_collections(chroma_proto::GetCollectionsRequest {
-                id: collection_id_str,
-                name,
-                limit: limit.map(|l| l as i32),
-                offset: Some(offset as i32),
-                tenant: tenant.unwrap_or("".to_string()),
-                database: database.unwrap_or("".to_string()),
+            .with_retry("get_collections", |mut client| {
+                let req = req.clone();
+                async move { client.get_collections(req).await }
             })
             .await;
 
@@ -716,13 +1051,43 @@ impl GrpcSysDb {
         }
     }
 
+    async fn get_collections_by_ids(
+        &mut self,
+        collection_ids: Vec<CollectionUuid>,
+    ) -> Result<Vec<Collection>, GetCollectionsError> {
+        let req = chroma_proto::GetCollectionsByIdsRequest {
+            collection_ids: collection_ids
+                .iter()
+                .map(|collection_id| collection_id.0.to_string())
+                .collect(),
+        };
+        let res = self
+            .with_retry("get_collections_by_ids", |mut client| {
+                let req = req.clone();
+                async move { client.get_collections_by_ids(req).await }
+            })
+            .await
+            .map_err(|e| GetCollectionsError::Internal(e.into()))?;
+        res.into_inner()
+            .collections
+            .into_iter()
+            .map(|proto_collection| proto_collection.try_into())
+            .collect::<Result<Vec<Collection>, CollectionConversionError>>()
+            .map_err(|e| GetCollectionsError::Internal(e.boxed()))
+    }
+
     async fn count_collections(
         &mut self,
         tenant: String,
         database: Option<String>,
     ) -> Result<usize, CountCollectionsError> {
         let request = chroma_proto::CountCollectionsRequest { tenant, database };
-        let res = self.client.count_collections(request).await;
+        let res = self
+            .with_retry("count_collections", |mut client| {
+                let request = request.clone();
+                async move { client.count_collections(request).await }
+            })
+            .await;
         match res {
             Ok(res) => Ok(res.into_inner().count as usize),
             Err(_) => Err(CountCollectionsError::Internal),
@@ -736,7 +1101,12 @@ impl GrpcSysDb {
         let request = chroma_proto::GetCollectionSizeRequest {
             id: collection_id.0.to_string(),
         };
-        let res = self.client.get_collection_size(request).await;
+        let res = self
+            .with_retry("get_collection_size", |mut client| {
+                let request = request.clone();
+                async move { client.get_collection_size(request).await }
+            })
+            .await;
         match res {
             Ok(res) => Ok(res.into_inner().total_records_post_compaction as usize),
             Err(e) => match e.code() {
@@ -800,7 +1170,7 @@ impl GrpcSysDb {
         name: Option<String>,
         metadata: Option<CollectionMetadataUpdate>,
         dimension: Option<u32>,
-    ) -> Result<(), UpdateCollectionError> {
+    ) -> Result<Collection, UpdateCollectionError> {
         let req = chroma_proto::UpdateCollectionRequest {
             id: collection_id.0.to_string(),
             name,
@@ -817,7 +1187,7 @@ impl GrpcSysDb {
             dimension: dimension.map(|dim| dim as i32),
         };
 
-        self.client.update_collection(req).await.map_err(|e| {
+        let res = self.client.update_collection(req).await.map_err(|e| {
             if e.code() == Code::NotFound {
                 UpdateCollectionError::NotFound(collection_id.to_string())
             } else {
@@ -825,7 +1195,16 @@ impl GrpcSysDb {
             }
         })?;
 
-        Ok(())
+        let collection = res
+            .into_inner()
+            .collection
+            .ok_or(UpdateCollectionError::Internal(
+                TonicMissingFieldError("collection").boxed(),
+            ))?
+            .try_into()
+            .map_err(|e: CollectionConversionError| UpdateCollectionError::Internal(e.boxed()))?;
+
+        Ok(collection)
     }
 
     async fn delete_collection(
@@ -857,8 +1236,11 @@ impl GrpcSysDb {
         &mut self,
     ) -> Result<Vec<CollectionToGcInfo>, GetCollectionsToGcError> {
         let res = self
-            .client
-            .list_collections_to_gc(chroma_proto::ListCollectionsToGcRequest {})
+            .with_retry("list_collections_to_gc", |mut client| async move {
+                client
+                    .list_collections_to_gc(chroma_proto::ListCollectionsToGcRequest {})
+                    .await
+            })
             .await;
 
         match res {
@@ -879,14 +1261,17 @@ impl GrpcSysDb {
         scope: Option<SegmentScope>,
         collection: CollectionUuid,
     ) -> Result<Vec<Segment>, GetSegmentsError> {
+        let req = chroma_proto::GetSegmentsRequest {
+            // TODO: modularize
+            id: id.as_ref().map(ToString::to_string),
+            r#type,
+            scope: scope.map(|x| x as i32),
+            collection: collection.to_string(),
+        };
         let res = self
-            .client
-            .get_segments(chroma_proto::GetSegmentsRequest {
-                // TODO: modularize
-                id: id.as_ref().map(ToString::to_string),
-                r#type,
-                scope: scope.map(|x| x as i32),
-                collection: collection.to_string(),
+            .with_retry("get_segments", |mut client| {
+                let req = req.clone();
+                async move { client.get_segments(req).await }
             })
             .await;
         match res {
@@ -910,10 +1295,13 @@ impl GrpcSysDb {
         &mut self,
         collection_id: CollectionUuid,
     ) -> Result<CollectionAndSegments, GetCollectionWithSegmentsError> {
+        let req = chroma_proto::GetCollectionWithSegmentsRequest {
+            id: collection_id.to_string(),
+        };
         let res = self
-            .client
-            .get_collection_with_segments(chroma_proto::GetCollectionWithSegmentsRequest {
-                id: collection_id.to_string(),
+            .with_retry("get_collection_with_segments", |mut client| {
+                let req = req.clone();
+                async move { client.get_collection_with_segments(req).await }
             })
             .await?
             .into_inner();
@@ -954,13 +1342,14 @@ impl GrpcSysDb {
         &mut self,
         tenant_ids: Vec<String>,
     ) -> Result<Vec<Tenant>, GetLastCompactionTimeError> {
+        let req = chroma_proto::GetLastCompactionTimeForTenantRequest {
+            tenant_id: tenant_ids,
+        };
         let res = self
-            .client
-            .get_last_compaction_time_for_tenant(
-                chroma_proto::GetLastCompactionTimeForTenantRequest {
-                    tenant_id: tenant_ids,
-                },
-            )
+            .with_retry("get_last_compaction_time", |mut client| {
+                let req = req.clone();
+                async move { client.get_last_compaction_time_for_tenant(req).await }
+            })
             .await;
         match res {
             Ok(res) => {
@@ -975,6 +1364,61 @@ impl GrpcSysDb {
         }
     }
 
+    async fn list_collection_versions(
+        &mut self,
+        collection_id: CollectionUuid,
+        tenant_id: String,
+    ) -> Result<Vec<chroma_proto::CollectionVersionInfo>, ListCollectionVersionsError> {
+        let req = chroma_proto::ListCollectionVersionsRequest {
+            collection_id: collection_id.to_string(),
+            tenant_id,
+            max_count: None,
+            versions_before: None,
+            versions_at_or_after: None,
+        };
+        let res = self
+            .with_retry("list_collection_versions", |mut client| {
+                let req = req.clone();
+                async move { client.list_collection_versions(req).await }
+            })
+            .await;
+        match res {
+            Ok(res) => Ok(res.into_inner().versions),
+            Err(e) if e.code() == Code::NotFound => Err(ListCollectionVersionsError::NotFound(
+                collection_id.to_string(),
+            )),
+            Err(e) => Err(ListCollectionVersionsError::RequestFailed(e)),
+        }
+    }
+
+    async fn set_last_compaction_time(
+        &mut self,
+        tenant_id: String,
+        last_compaction_time: i64,
+    ) -> Result<(), SetLastCompactionTimeError> {
+        let res = self
+            .client
+            .set_last_compaction_time_for_tenant(
+                chroma_proto::SetLastCompactionTimeForTenantRequest {
+                    tenant_last_compaction_time: Some(chroma_proto::TenantLastCompactionTime {
+                        tenant_id,
+                        last_compaction_time,
+                    }),
+                },
+            )
+            .await;
+        match res {
+            Ok(_) => Ok(()),
+            Err(e)
+                if e.code() == Code::NotFound
+                    || e.message().contains(COORDINATOR_TENANT_NOT_FOUND) =>
+            {
+                Err(SetLastCompactionTimeError::TenantNotFound)
+            }
+            Err(e) => Err(SetLastCompactionTimeError::FailedToSetLastCompactionTime(e)),
+        }
+    }
+
     async fn flush_compaction(
         &mut self,
         tenant_id: String,
@@ -1023,6 +1467,12 @@ impl GrpcSysDb {
                 };
                 Ok(res)
             }
+            Err(e)
+                if e.code() == Code::FailedPrecondition
+                    || e.message().contains(COORDINATOR_COLLECTION_VERSION_STALE) =>
+            {
+                Err(FlushCompactionError::VersionMismatch)
+            }
             Err(e) => Err(FlushCompactionError::FailedToFlushCompaction(e)),
         }
     }
@@ -1060,6 +1510,16 @@ impl GrpcSysDb {
     }
 }
 
+#[derive(Error, Debug)]
+#[error("{0} is not supported by this sysdb")]
+pub struct UnsupportedOperationError(pub &'static str);
+
+impl ChromaError for UnsupportedOperationError {
+    fn code(&self) -> ErrorCodes {
+        ErrorCodes::Unimplemented
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum GetLastCompactionTimeError {
     #[error("Failed to fetch")]
@@ -1067,13 +1527,58 @@ pub enum GetLastCompactionTimeError {
 
     #[error("Tenant not found in sysdb")]
     TenantNotFound,
+    #[error("Tenant compaction times are not supported by this sysdb")]
+    Unsupported,
 }
 
 impl ChromaError for GetLastCompactionTimeError {
     fn code(&self) -> ErrorCodes {
         match self {
             GetLastCompactionTimeError::FailedToGetLastCompactionTime(_) => ErrorCodes::Internal,
-            GetLastCompactionTimeError::TenantNotFound => ErrorCodes::Internal,
+            GetLastCompactionTimeError::TenantNotFound => ErrorCodes::NotFound,
+            GetLastCompactionTimeError::Unsupported => ErrorCodes::Unimplemented,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ListCollectionVersionsError {
+    #[error("Collection [{0}] not found")]
+    NotFound(String),
+    #[error("Grpc request failed")]
+    RequestFailed(#[from] tonic::Status),
+    #[error("Collection versions are not supported by this sysdb")]
+    Unsupported,
+}
+
+impl ChromaError for ListCollectionVersionsError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            ListCollectionVersionsError::NotFound(_) => ErrorCodes::NotFound,
+            ListCollectionVersionsError::RequestFailed(_) => ErrorCodes::Internal,
+            ListCollectionVersionsError::Unsupported => ErrorCodes::Unimplemented,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SetLastCompactionTimeError {
+    #[error("Failed to set")]
+    FailedToSetLastCompactionTime(#[from] tonic::Status),
+
+    #[error("Tenant not found in sysdb")]
+    TenantNotFound,
+
+    #[error("Setting the last compaction time is not supported by this sysdb")]
+    Unsupported,
+}
+
+impl ChromaError for SetLastCompactionTimeError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            SetLastCompactionTimeError::FailedToSetLastCompactionTime(_) => ErrorCodes::Internal,
+            SetLastCompactionTimeError::TenantNotFound => ErrorCodes::NotFound,
+            SetLastCompactionTimeError::Unsupported => ErrorCodes::Unimplemented,
         }
     }
 }
@@ -1090,6 +1595,20 @@ pub enum FlushCompactionError {
     CollectionNotFound,
     #[error("Segment not found in sysdb")]
     SegmentNotFound,
+    #[error("Insufficient space to flush compaction")]
+    InsufficientSpace,
+    #[error("Flush token not found")]
+    FlushTokenNotFound,
+    #[error("Segment file path must not be empty")]
+    InvalidPath,
+    #[error("Flush would change the collection dimension from {current} to {requested}")]
+    DimensionMismatch { current: i32, requested: i32 },
+    #[error("Flush failed validation: {0}")]
+    ValidationFailed(String),
+    #[error("Collection version does not match")]
+    VersionMismatch,
+    #[error("Flushing compactions is not supported by this sysdb")]
+    Unsupported,
 }
 
 impl ChromaError for FlushCompactionError {
@@ -1100,6 +1619,13 @@ impl ChromaError for FlushCompactionError {
             FlushCompactionError::FlushCompactionResponseConversionError(_) => ErrorCodes::Internal,
             FlushCompactionError::CollectionNotFound => ErrorCodes::Internal,
             FlushCompactionError::SegmentNotFound => ErrorCodes::Internal,
+            FlushCompactionError::InsufficientSpace => ErrorCodes::ResourceExhausted,
+            FlushCompactionError::FlushTokenNotFound => ErrorCodes::NotFound,
+            FlushCompactionError::InvalidPath => ErrorCodes::InvalidArgument,
+            FlushCompactionError::DimensionMismatch { .. } => ErrorCodes::InvalidArgument,
+            FlushCompactionError::ValidationFailed(_) => ErrorCodes::FailedPrecondition,
+            FlushCompactionError::VersionMismatch => ErrorCodes::VersionMismatch,
+            FlushCompactionError::Unsupported => ErrorCodes::Unimplemented,
         }
     }
 }
@@ -1108,12 +1634,24 @@ impl ChromaError for FlushCompactionError {
 pub enum MarkVersionForDeletionError {
     #[error("Failed to mark version for deletion")]
     FailedToMarkVersion(#[from] tonic::Status),
+    #[error("Epoch is older than the current epoch")]
+    EpochMismatch,
+    #[error("Version {version} of collection [{collection_id}] does not exist")]
+    VersionNotFound { collection_id: String, version: i64 },
+    #[error("Version {0} is the live version of its collection")]
+    LiveVersion(i64),
+    #[error("Collection versions are not supported by this sysdb")]
+    Unsupported,
 }
 
 impl ChromaError for MarkVersionForDeletionError {
     fn code(&self) -> ErrorCodes {
         match self {
             MarkVersionForDeletionError::FailedToMarkVersion(_) => ErrorCodes::Internal,
+            MarkVersionForDeletionError::EpochMismatch => ErrorCodes::FailedPrecondition,
+            MarkVersionForDeletionError::VersionNotFound { .. } => ErrorCodes::NotFound,
+            MarkVersionForDeletionError::LiveVersion(_) => ErrorCodes::FailedPrecondition,
+            MarkVersionForDeletionError::Unsupported => ErrorCodes::Unimplemented,
         }
     }
 }
@@ -1122,12 +1660,54 @@ impl ChromaError for MarkVersionForDeletionError {
 pub enum DeleteCollectionVersionError {
     #[error("Failed to delete version")]
     FailedToDeleteVersion(#[from] tonic::Status),
+    #[error("Collection versions are not supported by this sysdb")]
+    Unsupported,
 }
 
 impl ChromaError for DeleteCollectionVersionError {
     fn code(&self) -> ErrorCodes {
         match self {
             DeleteCollectionVersionError::FailedToDeleteVersion(_) => ErrorCodes::Internal,
+            DeleteCollectionVersionError::Unsupported => ErrorCodes::Unimplemented,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_not_found_is_not_found() {
+        assert_eq!(
+            GetLastCompactionTimeError::TenantNotFound.code(),
+            ErrorCodes::NotFound
+        );
+        assert_eq!(
+            SetLastCompactionTimeError::TenantNotFound.code(),
+            ErrorCodes::NotFound
+        );
+    }
+
+    #[test]
+    fn test_coordinator_error_messages_match_go() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../go/pkg/common/errors.go");
+        let errors = std::fs::read_to_string(path).expect("go/pkg/common/errors.go should exist");
+        for (name, message) in [
+            ("ErrTenantNotFound", COORDINATOR_TENANT_NOT_FOUND),
+            (
+                "ErrCollectionVersionStale",
+                COORDINATOR_COLLECTION_VERSION_STALE,
+            ),
+        ] {
+            let declaration = errors
+                .lines()
+                .find(|line| line.trim_start().starts_with(&format!("{name} ")))
+                .unwrap_or_else(|| panic!("{name} is not declared"));
+            assert!(
+                declaration.ends_with(&format!("errors.New(\"{message}\")")),
+                "{name} no longer reads \"{message}\": {declaration}"
+            );
         }
     }
 }
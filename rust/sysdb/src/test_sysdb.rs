@@ -1,298 +1,6588 @@
+use chroma_error::{ChromaError, ErrorCodes};
 use chroma_types::{
     Collection, CollectionUuid, Database, FlushCompactionResponse, GetCollectionSizeError,
-    GetSegmentsError, ListDatabasesError, ListDatabasesResponse, Segment, SegmentFlushInfo,
-    SegmentScope, SegmentType, Tenant,
+    GetSegmentsError, ListDatabasesError, ListDatabasesResponse, MetadataValue, Segment,
+    SegmentFlushInfo, SegmentScope, SegmentType, Tenant,
+};
+use chroma_types::{
+    CollectionMetadataUpdate, CountCollectionsError, CreateCollectionError, CreateDatabaseError,
+    CreateDatabaseResponse, CreateTenantError, CreateTenantResponse, DeleteCollectionError,
+    DeleteDatabaseError, DeleteDatabaseResponse, GetCollectionsError, GetDatabaseError,
+    GetDatabaseResponse, GetTenantError, GetTenantResponse, Metadata, SegmentUuid,
+    UpdateCollectionError,
 };
-use chroma_types::{GetCollectionsError, SegmentUuid};
 use parking_lot::Mutex;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::broadcast;
 
+use super::sysdb::CollectionToGcInfo;
 use super::sysdb::FlushCompactionError;
+use super::sysdb::GetCollectionsToGcError;
 use super::sysdb::GetLastCompactionTimeError;
+use super::sysdb::MarkVersionForDeletionError;
+use super::sysdb::SetLastCompactionTimeError;
+use chroma_types::chroma_proto;
 use chroma_types::chroma_proto::VersionListForCollection;
 
+/// Tenant flag that lets flushes through even when they carry a stale
+/// collection version. Strict checking is the default.
+pub const LENIENT_VERSION_CHECK_FLAG: &str = "lenient_version_check";
+
+/// A flush as seen by the test sysdb. Carries the same data as
+/// `SysDb::flush_compaction` plus test-only detail the real sysdb doesn't receive.
+#[derive(Clone, Debug)]
+pub struct FlushCompactionRequest {
+    pub tenant_id: String,
+    pub collection_id: CollectionUuid,
+    pub log_position: i64,
+    pub collection_version: i32,
+    pub segment_flush_info: Arc<[SegmentFlushInfo]>,
+    pub total_records_post_compaction: u64,
+    // Bytes written per segment by this flush.
+    pub segment_sizes: HashMap<SegmentUuid, u64>,
+    // Retries carrying an already seen key get the original response back.
+    pub idempotency_key: Option<String>,
+    // Dimension established by this flush. May only be set once.
+    pub dimension: Option<u32>,
+    // Compaction job that produced this flush.
+    pub job_id: Option<String>,
+}
+
+impl FlushCompactionRequest {
+    pub fn new(
+        tenant_id: String,
+        collection_id: CollectionUuid,
+        log_position: i64,
+        collection_version: i32,
+        segment_flush_info: Arc<[SegmentFlushInfo]>,
+        total_records_post_compaction: u64,
+    ) -> Self {
+        FlushCompactionRequest {
+            tenant_id,
+            collection_id,
+            log_position,
+            collection_version,
+            segment_flush_info,
+            total_records_post_compaction,
+            segment_sizes: HashMap::new(),
+            idempotency_key: None,
+            dimension: None,
+            job_id: None,
+        }
+    }
+}
+
+/// A collection and the segments that make it up.
+#[derive(Debug)]
+pub struct CollectionTopology {
+    pub collection: Collection,
+    // (scope, segment id, file count), ordered by scope.
+    pub segments: Vec<(SegmentScope, SegmentUuid, usize)>,
+}
+
+/// Store-wide totals. Soft-deleted collections are not counted.
+#[derive(Debug, Default, PartialEq)]
+pub struct AggregateStats {
+    pub total_collections: usize,
+    pub total_segments: usize,
+    pub total_records: u64,
+    pub total_versions: usize,
+    pub distinct_tenants: usize,
+    pub distinct_databases: usize,
+}
+
+/// Every file a collection's segments reference.
+#[derive(Debug, PartialEq)]
+pub struct CollectionManifest {
+    pub collection_id: CollectionUuid,
+    pub version: i32,
+    pub segments: BTreeMap<SegmentUuid, HashMap<String, Vec<String>>>,
+}
+
+/// How the segment files of two collections overlap.
+#[derive(Debug, PartialEq)]
+pub struct SegmentPathDiff {
+    pub only_a: Vec<String>,
+    pub only_b: Vec<String>,
+    pub shared: Vec<String>,
+}
+
+/// Predicates a collection must all satisfy. Unset fields match anything.
+#[derive(Clone, Debug, Default)]
+pub struct CollectionFilter {
+    pub collection_id: Option<CollectionUuid>,
+    pub name: Option<String>,
+    pub tenant: Option<String>,
+    pub database: Option<String>,
+    pub version: Option<i32>,
+    // Key/value pairs the collection's metadata must all contain.
+    pub metadata: Option<Metadata>,
+    // Also match soft-deleted collections. Only honored by `find_collections`.
+    pub include_deleted: bool,
+}
+
+/// Everything the test sysdb holds for a single tenant.
+#[derive(Debug)]
+pub struct TenantSnapshot {
+    pub collections: Vec<Collection>,
+    pub segments: Vec<Segment>,
+    pub databases: Vec<Database>,
+    pub last_compaction_time: Option<i64>,
+}
+
+/// A call made against the test sysdb, in the order it was made. Mutations are
+/// recorded once they succeed.
+#[derive(Clone, Debug)]
+pub enum SysDbCall {
+    AddCollection(Collection),
+    AddSegment(Segment),
+    CreateDatabase {
+        database_id: uuid::Uuid,
+        name: String,
+        tenant: String,
+    },
+    SetNow(i64),
+    FlushCompaction(FlushCompactionRequest),
+    GetCollections(CollectionFilter),
+    GetSegments {
+        id: Option<SegmentUuid>,
+        r#type: Option<String>,
+        scope: Option<SegmentScope>,
+        collection: CollectionUuid,
+    },
+    ListDatabases {
+        tenant: String,
+        limit: Option<u32>,
+        offset: u32,
+    },
+}
+
+impl SysDbCall {
+    /// Whether the call leaves the sysdb unchanged.
+    pub fn is_read(&self) -> bool {
+        matches!(
+            self,
+            SysDbCall::GetCollections(_)
+                | SysDbCall::GetSegments { .. }
+                | SysDbCall::ListDatabases { .. }
+        )
+    }
+}
+
+/// An error returned by the next call to the method it names, in place of
+/// running the call.
+#[derive(Debug)]
+pub enum InjectedError {
+    GetCollections(GetCollectionsError),
+    GetSegments(GetSegmentsError),
+    FlushCompaction(FlushCompactionError),
+    GetLastCompactionTime(GetLastCompactionTimeError),
+}
+
+impl InjectedError {
+    pub fn method(&self) -> SysDbMethod {
+        match self {
+            InjectedError::GetCollections(_) => SysDbMethod::GetCollections,
+            InjectedError::GetSegments(_) => SysDbMethod::GetSegments,
+            InjectedError::FlushCompaction(_) => SysDbMethod::FlushCompaction,
+            InjectedError::GetLastCompactionTime(_) => SysDbMethod::GetLastCompactionTime,
+        }
+    }
+}
+
+/// A method errors or latency can be injected into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SysDbMethod {
+    GetCollections,
+    GetSegments,
+    FlushCompaction,
+    GetLastCompactionTime,
+}
+
+// Injected errors are not `Clone`, so copies of the store start without any.
+#[derive(Debug, Default)]
+struct InjectedErrors(Vec<InjectedError>);
+
+impl Clone for InjectedErrors {
+    fn clone(&self) -> Self {
+        InjectedErrors::default()
+    }
+}
+
+impl From<VersionRecord> for chroma_proto::CollectionVersionInfo {
+    fn from(record: VersionRecord) -> Self {
+        let segment_compaction_info = record
+            .segments
+            .iter()
+            .map(|segment| chroma_proto::FlushSegmentCompactionInfo {
+                segment_id: segment.id.to_string(),
+                file_paths: segment
+                    .file_path
+                    .iter()
+                    .map(|(key, paths)| {
+                        (
+                            key.clone(),
+                            chroma_proto::FilePaths {
+                                paths: paths.clone(),
+                            },
+                        )
+                    })
+                    .collect(),
+            })
+            .collect();
+        chroma_proto::CollectionVersionInfo {
+            version: record.version as i64,
+            segment_info: Some(chroma_proto::CollectionSegmentInfo {
+                segment_compaction_info,
+            }),
+            collection_info_mutable: Some(chroma_proto::CollectionInfoMutable {
+                current_log_position: record.collection.log_position,
+                current_collection_version: record.version as i64,
+                updated_at_secs: record.created_at,
+                last_compaction_time_secs: record.created_at,
+                dimension: record.collection.dimension.unwrap_or_default() as i64,
+            }),
+            created_at_secs: record.created_at,
+            marked_for_deletion: record.marked_for_deletion,
+            ..Default::default()
+        }
+    }
+}
+
+/// What happened to a collection, as delivered to subscribers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SysDbEventKind {
+    Created,
+    Flushed { version: i32 },
+    Deleted,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SysDbEvent {
+    pub collection_id: CollectionUuid,
+    pub kind: SysDbEventKind,
+}
+
+// Events a subscriber may fall behind by before it starts lagging.
+const EVENT_CAPACITY: usize = 1024;
+
+// Copies of the store get their own channel so their subscribers only see
+// their own changes.
+#[derive(Debug)]
+struct EventSender(broadcast::Sender<SysDbEvent>);
+
+impl Default for EventSender {
+    fn default() -> Self {
+        EventSender(broadcast::channel(EVENT_CAPACITY).0)
+    }
+}
+
+impl Clone for EventSender {
+    fn clone(&self) -> Self {
+        EventSender::default()
+    }
+}
+
+/// A check run against every flush, returning the reason for any rejection.
+pub type FlushValidatorFn = dyn Fn(&FlushCompactionRequest) -> Result<(), String> + Send + Sync;
+
+#[derive(Clone)]
+struct FlushValidator(Arc<FlushValidatorFn>);
+
+impl std::fmt::Debug for FlushValidator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FlushValidator")
+    }
+}
+
+/// Handle to a flush staged with `prepare_flush`, applied by `commit_flush`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FlushToken(uuid::Uuid);
+
+/// The state of a collection as of one of its versions.
+#[derive(Clone, Debug)]
+pub struct VersionRecord {
+    pub version: i32,
+    pub collection: Collection,
+    pub created_at: i64,
+    // The collection's segments right after the flush that made this version.
+    pub segments: Vec<Segment>,
+    pub job_id: Option<String>,
+    // Set by `mark_version_for_deletion`. Only marked versions may be deleted.
+    pub marked_for_deletion: bool,
+}
+
+/// A successful flush, as recorded in the compaction log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompactionRecord {
+    pub tenant_id: String,
+    pub collection_id: CollectionUuid,
+    pub log_position: i64,
+    pub collection_version: i32,
+    pub total_records_post_compaction: u64,
+}
+
+/// A collection with its segments and version history, as taken by a backup.
+#[derive(Clone, Debug)]
+pub struct CollectionSnapshot {
+    pub collection: Collection,
+    pub segments: Vec<Segment>,
+    pub versions: Vec<VersionRecord>,
+    // Replace a collection with the same id instead of failing.
+    pub overwrite: bool,
+}
+
+/// Sequence number of every collection at one point in time.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SysDbSnapshot {
+    pub seqnos: HashMap<CollectionUuid, u64>,
+}
+
+impl SysDbSnapshot {
+    /// Whether no collection in this snapshot is ahead of, or missing from, `other`.
+    pub fn happens_before(&self, other: &SysDbSnapshot) -> bool {
+        self.seqnos.iter().all(|(collection_id, seqno)| {
+            other
+                .seqnos
+                .get(collection_id)
+                .is_some_and(|other_seqno| seqno <= other_seqno)
+        })
+    }
+}
+
+/// Versions a GC pass may delete, per collection.
+#[derive(Debug, Default)]
+pub struct GcPlan {
+    pub collections: Vec<CollectionGcPlan>,
+}
+
+#[derive(Debug)]
+pub struct CollectionGcPlan {
+    pub collection_id: CollectionUuid,
+    pub versions: Vec<i32>,
+    // Files referenced only by the deletable versions.
+    pub unreferenced_paths: Vec<String>,
+}
+
 #[derive(Clone, Debug)]
 pub struct TestSysDb {
     inner: Arc<Mutex<Inner>>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct Inner {
     collections: HashMap<CollectionUuid, Collection>,
     segments: HashMap<SegmentUuid, Segment>,
     tenant_last_compaction_time: HashMap<String, i64>,
+    tenant_flags: HashMap<String, HashSet<String>>,
+    // Maps a principal to the set of "tenant:database" strings it can access.
+    grants: HashMap<String, HashSet<String>>,
+    flush_bytes_written: HashMap<CollectionUuid, u64>,
+    flush_responses: HashMap<String, FlushCompactionResponse>,
+    // Tenant each collection had when it was first added.
+    original_tenants: HashMap<CollectionUuid, String>,
+    // Sequence number stamped on each collection when it is first added.
+    creation_seq: HashMap<CollectionUuid, u64>,
+    next_creation_seq: u64,
+    flush_validator: Option<FlushValidator>,
+    // How long a flush sleeps between updating the collection and its segments.
+    flush_pause: Option<Duration>,
+    // Upper bound on the bytes a single flush may write. Unlimited when unset.
+    available_space: Option<u64>,
+    // Every version produced by a flush, oldest first.
+    versions: HashMap<CollectionUuid, Vec<VersionRecord>>,
+    max_version_history: Option<usize>,
+    // Versions GC must never delete.
+    protected_versions: HashMap<CollectionUuid, HashSet<i32>>,
+    pending_flushes: HashMap<FlushToken, FlushCompactionRequest>,
+    // Soft-deleted collections and the clock value they were deleted at. They
+    // stay in `collections` but are hidden from reads.
+    deleted_collections: HashMap<CollectionUuid, i64>,
+    // Record counts as of the last `collections_with_size_change` call.
+    last_observed_size: HashMap<CollectionUuid, u64>,
+    // When set, segment reads fail while collection reads keep working.
+    segment_read_failure: bool,
+    // Path of each collection's version file and the version it was written at.
+    version_files: HashMap<CollectionUuid, (String, i32)>,
+    // GC epoch. Marks carrying an older epoch are rejected.
+    epoch: i64,
+    auto_pruned_versions: HashMap<CollectionUuid, Vec<i32>>,
+    // Logical clock in seconds, advanced only by the test.
+    now: i64,
+    segment_updated_at: HashMap<SegmentUuid, i64>,
+    // (log position, write time) of each record appended to a collection's log.
+    log_writes: HashMap<CollectionUuid, Vec<(i64, i64)>>,
+    // Clock value of each collection's latest flush.
+    last_compaction_at: HashMap<CollectionUuid, i64>,
+    // Collections compacted less than this long ago are not compaction candidates.
+    compaction_cooldown: Duration,
+    // Clock value of every flush, per tenant.
+    tenant_flush_times: HashMap<String, Vec<i64>>,
+    // Every database seen so far, keyed by id. Ids are assigned once so
+    // repeated listings agree.
+    databases: HashMap<uuid::Uuid, Database>,
+    // Id of each database by (tenant, name).
+    database_ids: HashMap<(String, String), uuid::Uuid>,
+    call_log: Vec<SysDbCall>,
+    // Bumped whenever a collection, one of its segments, or its flush state changes.
+    seqnos: HashMap<CollectionUuid, u64>,
+    // Collection each fork was made from.
+    fork_parents: HashMap<CollectionUuid, CollectionUuid>,
+    // When set, ids the sysdb generates itself are derived from this seed.
+    uuid_seed: Option<u64>,
+    next_uuid: u64,
+    injected_errors: InjectedErrors,
+    // How long each call to a method waits before doing anything.
+    latencies: HashMap<SysDbMethod, Duration>,
+    // Successful flushes not yet taken by the test, oldest first.
+    compaction_log: Vec<CompactionRecord>,
+    // Records each segment holds, as set by the test.
+    segment_record_counts: HashMap<SegmentUuid, u64>,
+    events: EventSender,
 }
 
-impl TestSysDb {
-    #[allow(clippy::new_without_default)]
-    pub fn new() -> Self {
-        TestSysDb {
-            inner: Arc::new(Mutex::new(Inner {
-                collections: HashMap::new(),
-                segments: HashMap::new(),
-                tenant_last_compaction_time: HashMap::new(),
-            })),
+impl Inner {
+    fn new(uuid_seed: Option<u64>) -> Self {
+        Inner {
+            collections: HashMap::new(),
+            segments: HashMap::new(),
+            tenant_last_compaction_time: HashMap::new(),
+            tenant_flags: HashMap::new(),
+            grants: HashMap::new(),
+            flush_bytes_written: HashMap::new(),
+            flush_responses: HashMap::new(),
+            original_tenants: HashMap::new(),
+            creation_seq: HashMap::new(),
+            next_creation_seq: 0,
+            flush_validator: None,
+            flush_pause: None,
+            available_space: None,
+            versions: HashMap::new(),
+            max_version_history: None,
+            protected_versions: HashMap::new(),
+            pending_flushes: HashMap::new(),
+            deleted_collections: HashMap::new(),
+            last_observed_size: HashMap::new(),
+            segment_read_failure: false,
+            version_files: HashMap::new(),
+            epoch: 0,
+            auto_pruned_versions: HashMap::new(),
+            now: 0,
+            segment_updated_at: HashMap::new(),
+            log_writes: HashMap::new(),
+            last_compaction_at: HashMap::new(),
+            compaction_cooldown: Duration::ZERO,
+            tenant_flush_times: HashMap::new(),
+            databases: HashMap::new(),
+            database_ids: HashMap::new(),
+            call_log: Vec::new(),
+            seqnos: HashMap::new(),
+            fork_parents: HashMap::new(),
+            uuid_seed,
+            next_uuid: 0,
+            injected_errors: InjectedErrors::default(),
+            latencies: HashMap::new(),
+            compaction_log: Vec::new(),
+            segment_record_counts: HashMap::new(),
+            events: EventSender::default(),
         }
     }
 
-    pub fn add_collection(&mut self, collection: Collection) {
-        let mut inner = self.inner.lock();
-        inner
-            .collections
-            .insert(collection.collection_id, collection);
+    fn new_uuid(&mut self) -> uuid::Uuid {
+        let Some(seed) = self.uuid_seed else {
+            return uuid::Uuid::new_v4();
+        };
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&seed.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.next_uuid.to_be_bytes());
+        self.next_uuid += 1;
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
     }
 
-    pub fn update_collection_size(&mut self, collection_id: CollectionUuid, collection_size: u64) {
-        let mut inner = self.inner.lock();
-        let coll = inner
-            .collections
-            .get_mut(&collection_id)
-            .expect("Expected collection");
-        coll.total_records_post_compaction = collection_size;
+    // Returns the database, giving it an id the first time it is seen.
+    fn database(&mut self, tenant: &str, name: &str) -> Database {
+        if let Some(database) = self.database_named(tenant, name) {
+            return database.clone();
+        }
+        let database = Database {
+            id: self.new_uuid(),
+            name: name.to_string(),
+            tenant: tenant.to_string(),
+        };
+        self.insert_database(database.clone());
+        database
     }
 
-    pub fn add_segment(&mut self, segment: Segment) {
-        let mut inner = self.inner.lock();
-        inner.segments.insert(segment.id, segment);
+    fn database_named(&self, tenant: &str, name: &str) -> Option<&Database> {
+        self.database_ids
+            .get(&(tenant.to_string(), name.to_string()))
+            .and_then(|id| self.databases.get(id))
     }
 
-    pub fn add_tenant_last_compaction_time(&mut self, tenant: String, last_compaction_time: i64) {
-        let mut inner = self.inner.lock();
-        inner
-            .tenant_last_compaction_time
-            .insert(tenant, last_compaction_time);
+    fn insert_database(&mut self, database: Database) {
+        self.database_ids.insert(
+            (database.tenant.clone(), database.name.clone()),
+            database.id,
+        );
+        self.databases.insert(database.id, database);
     }
 
-    fn filter_collections(
-        collection: &Collection,
-        collection_id: Option<CollectionUuid>,
-        name: Option<String>,
-        tenant: Option<String>,
-        database: Option<String>,
-    ) -> bool {
-        if collection_id.is_some() && collection_id.unwrap() != collection.collection_id {
-            return false;
-        }
-        if name.is_some() && name.unwrap() != collection.name {
-            return false;
+    // Nobody may be subscribed, so send errors are expected.
+    fn notify(&self, collection_id: CollectionUuid, kind: SysDbEventKind) {
+        let _ = self.events.0.send(SysDbEvent {
+            collection_id,
+            kind,
+        });
+    }
+
+    /// Writes the segments of a flush whose collection update has already
+    /// been applied, and records the flush.
+    fn finish_flush(
+        &mut self,
+        request: FlushCompactionRequest,
+        collection: Collection,
+        last_compaction_time: i64,
+    ) -> FlushCompactionResponse {
+        let collection_id = request.collection_id;
+        let new_collection_version = collection.version;
+        let now = self.now;
+        for segment_flush_info in request.segment_flush_info.iter() {
+            // Segments were checked before the collection was updated; one can
+            // only be missing here if it was deleted during a flush pause.
+            let Some(segment) = self.segments.get(&segment_flush_info.segment_id) else {
+                continue;
+            };
+            let mut segment = segment.clone();
+            segment.file_path = segment_flush_info.file_paths.clone();
+            self.segment_updated_at.insert(segment.id, now);
+            self.segments.insert(segment.id, segment);
         }
-        if tenant.is_some() && tenant.unwrap() != collection.tenant {
-            return false;
+        *self.flush_bytes_written.entry(collection_id).or_insert(0) +=
+            request.segment_sizes.values().sum::<u64>();
+        self.last_compaction_at.insert(collection_id, now);
+        self.tenant_flush_times
+            .entry(request.tenant_id.clone())
+            .or_default()
+            .push(now);
+        let segments = self
+            .segments
+            .values()
+            .filter(|segment| segment.collection == collection_id)
+            .cloned()
+            .collect();
+        let max_version_history = self.max_version_history;
+        let versions = self.versions.entry(collection_id).or_default();
+        versions.push(VersionRecord {
+            version: new_collection_version,
+            collection,
+            created_at: now,
+            segments,
+            job_id: request.job_id.clone(),
+            marked_for_deletion: false,
+        });
+        if let Some(max_version_history) = max_version_history {
+            if versions.len() > max_version_history {
+                let pruned = versions
+                    .drain(..versions.len() - max_version_history)
+                    .map(|record| record.version)
+                    .collect::<Vec<_>>();
+                self.auto_pruned_versions
+                    .entry(collection_id)
+                    .or_default()
+                    .extend(pruned);
+            }
         }
-        if database.is_some() && database.unwrap() != collection.database {
-            return false;
+
+        let response = FlushCompactionResponse::new(
+            collection_id,
+            new_collection_version,
+            last_compaction_time,
+        );
+        if let Some(key) = &request.idempotency_key {
+            self.flush_responses.insert(key.clone(), response.clone());
         }
-        true
+        self.compaction_log.push(CompactionRecord {
+            tenant_id: request.tenant_id.clone(),
+            collection_id,
+            log_position: request.log_position,
+            collection_version: new_collection_version,
+            total_records_post_compaction: request.total_records_post_compaction,
+        });
+        self.call_log.push(SysDbCall::FlushCompaction(request));
+        *self.seqnos.entry(collection_id).or_insert(0) += 1;
+        self.notify(
+            collection_id,
+            SysDbEventKind::Flushed {
+                version: new_collection_version,
+            },
+        );
+        response
     }
 
-    fn filter_segments(
-        segment: &Segment,
-        id: Option<SegmentUuid>,
-        r#type: Option<String>,
-        scope: Option<SegmentScope>,
-        collection: CollectionUuid,
-    ) -> bool {
-        if id.is_some() && id.unwrap() != segment.id {
-            return false;
-        }
-        if let Some(r#type) = r#type {
-            return segment.r#type == SegmentType::try_from(r#type.as_str()).unwrap();
-        }
-        if scope.is_some() && scope.unwrap() != segment.scope {
-            return false;
+    fn insert_collection(&mut self, collection: Collection) {
+        self.original_tenants
+            .entry(collection.collection_id)
+            .or_insert_with(|| collection.tenant.clone());
+        if !self.creation_seq.contains_key(&collection.collection_id) {
+            let seq = self.next_creation_seq;
+            self.creation_seq.insert(collection.collection_id, seq);
+            self.next_creation_seq += 1;
         }
-        if collection != segment.collection {
-            return false;
+        self.database(&collection.tenant, &collection.database);
+        self.call_log
+            .push(SysDbCall::AddCollection(collection.clone()));
+        *self.seqnos.entry(collection.collection_id).or_insert(0) += 1;
+        let collection_id = collection.collection_id;
+        if self.collections.insert(collection_id, collection).is_none() {
+            self.notify(collection_id, SysDbEventKind::Created);
         }
-        true
+    }
+
+    fn insert_segment(&mut self, segment: Segment) {
+        self.segment_updated_at.insert(segment.id, self.now);
+        self.call_log.push(SysDbCall::AddSegment(segment.clone()));
+        *self.seqnos.entry(segment.collection).or_insert(0) += 1;
+        self.segments.insert(segment.id, segment);
+    }
+
+    /// Every collection that has not been soft deleted. Readers go through
+    /// this (or [`Inner::live_collection`]) so a soft-deleted collection is
+    /// hidden everywhere, not just from the lookups that remembered to check.
+    fn live_collections(&self) -> impl Iterator<Item = &Collection> + '_ {
+        self.collections.values().filter(|collection| {
+            !self
+                .deleted_collections
+                .contains_key(&collection.collection_id)
+        })
+    }
+
+    fn live_collection(&self, collection_id: &CollectionUuid) -> Option<&Collection> {
+        self.collections
+            .get(collection_id)
+            .filter(|_| !self.deleted_collections.contains_key(collection_id))
+    }
+
+    fn live_collection_named(&self, collection: &Collection) -> Option<&Collection> {
+        self.live_collections().find(|existing| {
+            existing.tenant == collection.tenant
+                && existing.database == collection.database
+                && existing.name == collection.name
+        })
     }
 }
 
 impl TestSysDb {
-    pub(crate) async fn get_collections(
-        &mut self,
-        collection_id: Option<CollectionUuid>,
-        name: Option<String>,
-        tenant: Option<String>,
-        database: Option<String>,
-    ) -> Result<Vec<Collection>, GetCollectionsError> {
-        let inner = self.inner.lock();
-        let mut collections = Vec::new();
-        for collection in inner.collections.values() {
-            if !TestSysDb::filter_collections(
-                collection,
-                collection_id,
-                name.clone(),
-                tenant.clone(),
-                database.clone(),
-            ) {
-                continue;
-            }
-            collections.push(collection.clone());
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        TestSysDb {
+            inner: Arc::new(Mutex::new(Inner::new(None))),
         }
-        Ok(collections)
     }
 
-    pub(crate) async fn get_segments(
-        &mut self,
-        id: Option<SegmentUuid>,
-        r#type: Option<String>,
-        scope: Option<SegmentScope>,
-        collection: CollectionUuid,
-    ) -> Result<Vec<Segment>, GetSegmentsError> {
-        let inner = self.inner.lock();
-        let mut segments = Vec::new();
-        for segment in inner.segments.values() {
-            if !TestSysDb::filter_segments(segment, id, r#type.clone(), scope.clone(), collection) {
-                continue;
-            }
-            segments.push(segment.clone());
+    /// Like `new`, except every id the sysdb generates itself, such as database
+    /// ids, is derived from `seed` and so repeats from run to run.
+    pub fn with_seed(seed: u64) -> Self {
+        TestSysDb {
+            inner: Arc::new(Mutex::new(Inner::new(Some(seed)))),
         }
-        Ok(segments)
     }
 
-    pub(crate) async fn list_databases(
-        &self,
-        tenant: String,
-        limit: Option<u32>,
-        _offset: u32,
-    ) -> Result<ListDatabasesResponse, ListDatabasesError> {
-        let inner = self.inner.lock();
-        let mut databases = Vec::new();
-        let mut seen_db_names = std::collections::HashSet::new();
+    /// Drops all state so the instance can be reused. A seeded sysdb starts
+    /// generating the same ids again.
+    pub fn reset(&mut self) {
+        let mut inner = self.inner.lock();
+        *inner = Inner::new(inner.uuid_seed);
+    }
 
-        for collection in inner.collections.values() {
-            if collection.tenant == tenant && !seen_db_names.contains(&collection.database) {
-                seen_db_names.insert(collection.database.clone());
+    /// Makes the next call to the method named by `error` fail with it. Queued
+    /// errors for the same method are returned in order, one per call.
+    pub fn fail_next(&mut self, error: InjectedError) {
+        let mut inner = self.inner.lock();
+        inner.injected_errors.0.push(error);
+    }
 
-                let db = Database {
-                    id: uuid::Uuid::new_v4(),
-                    name: collection.database.clone(),
-                    tenant: tenant.clone(),
-                };
+    /// Makes the next `n` calls to the method fail, each with a fresh error.
+    pub fn fail_times(&mut self, n: usize, error: impl Fn() -> InjectedError) {
+        let mut inner = self.inner.lock();
+        inner.injected_errors.0.extend((0..n).map(|_| error()));
+    }
 
-                databases.push(db);
-            }
+    /// Delays every later call to `method` by `latency`, before any injected
+    /// error is returned. A zero latency removes the delay.
+    pub fn set_latency(&mut self, method: SysDbMethod, latency: Duration) {
+        let mut inner = self.inner.lock();
+        if latency.is_zero() {
+            inner.latencies.remove(&method);
+        } else {
+            inner.latencies.insert(method, latency);
         }
+    }
 
-        if let Some(limit_value) = limit {
-            if limit_value > 0 && databases.len() > limit_value as usize {
-                databases.truncate(limit_value as usize);
-            }
+    async fn simulate_latency(&self, method: SysDbMethod) {
+        let latency = self.inner.lock().latencies.get(&method).copied();
+        if let Some(latency) = latency {
+            tokio::time::sleep(latency).await;
         }
+    }
 
-        Ok(databases)
+    fn take_injected(&self, method: SysDbMethod) -> Option<InjectedError> {
+        let mut inner = self.inner.lock();
+        let index = inner
+            .injected_errors
+            .0
+            .iter()
+            .position(|error| error.method() == method)?;
+        Some(inner.injected_errors.0.remove(index))
     }
 
-    pub(crate) async fn get_last_compaction_time(
-        &mut self,
-        tenant_ids: Vec<String>,
-    ) -> Result<Vec<Tenant>, GetLastCompactionTimeError> {
+    /// Unlike `clone`, which shares state, returns an independent copy of the
+    /// store.
+    pub fn deep_clone(&self) -> TestSysDb {
         let inner = self.inner.lock();
-        let mut tenants = Vec::new();
-        for tenant_id in tenant_ids {
-            let last_compaction_time = match inner.tenant_last_compaction_time.get(&tenant_id) {
-                Some(last_compaction_time) => *last_compaction_time,
-                None => {
-                    return Err(GetLastCompactionTimeError::TenantNotFound);
-                }
-            };
-            tenants.push(Tenant {
-                id: tenant_id,
-                last_compaction_time,
-            });
+        TestSysDb {
+            inner: Arc::new(Mutex::new(inner.clone())),
         }
-        Ok(tenants)
     }
 
-    pub(crate) async fn flush_compaction(
+    pub fn add_collection(&mut self, collection: Collection) {
+        self.inner.lock().insert_collection(collection);
+    }
+
+    pub fn update_collection_size(&mut self, collection_id: CollectionUuid, collection_size: u64) {
+        let mut inner = self.inner.lock();
+        let coll = inner
+            .collections
+            .get_mut(&collection_id)
+            .expect("Expected collection");
+        coll.total_records_post_compaction = collection_size;
+    }
+
+    /// Applies the provided fields to a collection and leaves the rest as is.
+    /// Metadata is replaced as a whole, as the sqlite sysdb does.
+    pub async fn update_collection(
         &mut self,
-        tenant_id: String,
         collection_id: CollectionUuid,
-        log_position: i64,
-        collection_version: i32,
-        segment_flush_info: Arc<[SegmentFlushInfo]>,
-        total_records_post_compaction: u64,
-    ) -> Result<FlushCompactionResponse, FlushCompactionError> {
+        name: Option<String>,
+        metadata: Option<CollectionMetadataUpdate>,
+        dimension: Option<u32>,
+    ) -> Result<Collection, UpdateCollectionError> {
         let mut inner = self.inner.lock();
-        let collection = inner.collections.get(&collection_id);
-        if collection.is_none() {
-            return Err(FlushCompactionError::CollectionNotFound);
+        let collection = match inner.live_collection(&collection_id) {
+            Some(collection) => collection.clone(),
+            None => return Err(UpdateCollectionError::NotFound(collection_id.to_string())),
+        };
+        if let Some(name) = &name {
+            let taken = inner.live_collections().any(|existing| {
+                existing.collection_id != collection_id
+                    && existing.tenant == collection.tenant
+                    && existing.database == collection.database
+                    && existing.name == *name
+            });
+            if taken {
+                return Err(UpdateCollectionError::AlreadyExists(name.clone()));
+            }
         }
-        let collection = collection.unwrap();
-        let mut collection = collection.clone();
-        collection.log_position = log_position;
-        let new_collection_version = collection_version + 1;
-        collection.version = new_collection_version;
-        collection.total_records_post_compaction = total_records_post_compaction;
-        inner
-            .collections
-            .insert(collection.collection_id, collection);
-        let mut last_compaction_time = match inner.tenant_last_compaction_time.get(&tenant_id) {
-            Some(last_compaction_time) => *last_compaction_time,
-            None => 0,
+
+        let mut collection = collection;
+        if let Some(name) = name {
+            collection.name = name;
+        }
+        if let Some(metadata) = metadata {
+            collection.metadata = match metadata {
+                CollectionMetadataUpdate::ResetMetadata => None,
+                CollectionMetadataUpdate::UpdateMetadata(metadata) => {
+                    let metadata = metadata
+                        .iter()
+                        .filter_map(|(key, value)| {
+                            MetadataValue::try_from(value)
+                                .ok()
+                                .map(|value| (key.clone(), value))
+                        })
+                        .collect::<Metadata>();
+                    (!metadata.is_empty()).then_some(metadata)
+                }
+            };
+        }
+        if let Some(dimension) = dimension {
+            collection.dimension = Some(dimension as i32);
+        }
+        inner.collections.insert(collection_id, collection.clone());
+        *inner.seqnos.entry(collection_id).or_insert(0) += 1;
+        Ok(collection)
+    }
+
+    pub fn add_segment(&mut self, segment: Segment) {
+        self.inner.lock().insert_segment(segment);
+    }
+
+    /// Creates a collection that starts as a copy of `source_id` at its current
+    /// version. The copy's segments get fresh ids but keep the same files.
+    pub fn fork_collection(
+        &mut self,
+        source_id: CollectionUuid,
+        new_id: CollectionUuid,
+        new_name: String,
+    ) -> Result<Collection, ForkCollectionError> {
+        let (fork, segments) = {
+            let mut inner = self.inner.lock();
+            let source = match inner.live_collection(&source_id) {
+                Some(source) => source,
+                None => return Err(ForkCollectionError::NotFound(source_id)),
+            };
+            if inner.collections.contains_key(&new_id) {
+                return Err(ForkCollectionError::AlreadyExists(new_id));
+            }
+            let fork = Collection {
+                collection_id: new_id,
+                name: new_name,
+                ..source.clone()
+            };
+            let source_segments = inner
+                .segments
+                .values()
+                .filter(|segment| segment.collection == source_id)
+                .cloned()
+                .collect::<Vec<_>>();
+            let segments = source_segments
+                .into_iter()
+                .map(|segment| Segment {
+                    id: SegmentUuid(inner.new_uuid()),
+                    collection: new_id,
+                    ..segment
+                })
+                .collect::<Vec<_>>();
+            (fork, segments)
         };
-        last_compaction_time += 1;
+        self.add_collection(fork.clone());
+        for segment in segments {
+            self.add_segment(segment);
+        }
+        self.inner.lock().fork_parents.insert(new_id, source_id);
+        Ok(fork)
+    }
 
-        // update segments
-        for segment_flush_info in segment_flush_info.iter() {
-            let segment = inner.segments.get(&segment_flush_info.segment_id);
-            if segment.is_none() {
-                return Err(FlushCompactionError::SegmentNotFound);
+    pub fn fork_parent(&self, collection_id: CollectionUuid) -> Option<CollectionUuid> {
+        let inner = self.inner.lock();
+        inner.fork_parents.get(&collection_id).copied()
+    }
+
+    pub fn create_segment(&mut self, segment: Segment) -> Result<(), CreateSegmentError> {
+        if self.inner.lock().segments.contains_key(&segment.id) {
+            return Err(CreateSegmentError::AlreadyExists(segment.id));
+        }
+        self.add_segment(segment);
+        Ok(())
+    }
+
+    pub fn delete_segment(&mut self, id: SegmentUuid) -> Result<(), DeleteSegmentError> {
+        let mut inner = self.inner.lock();
+        let segment = inner
+            .segments
+            .remove(&id)
+            .ok_or(DeleteSegmentError::NotFound(id))?;
+        inner.segment_updated_at.remove(&id);
+        *inner.seqnos.entry(segment.collection).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /// Deletes every segment of a collection, returning how many there were.
+    pub fn delete_segments_for_collection(&mut self, collection_id: CollectionUuid) -> usize {
+        let mut inner = self.inner.lock();
+        let ids = inner
+            .segments
+            .values()
+            .filter(|segment| segment.collection == collection_id)
+            .map(|segment| segment.id)
+            .collect::<Vec<_>>();
+        for id in &ids {
+            inner.segments.remove(id);
+            inner.segment_updated_at.remove(id);
+        }
+        if !ids.is_empty() {
+            *inner.seqnos.entry(collection_id).or_insert(0) += 1;
+        }
+        ids.len()
+    }
+
+    pub fn soft_delete_collections_by_metadata(
+        &mut self,
+        key: String,
+        value: MetadataValue,
+    ) -> usize {
+        let mut inner = self.inner.lock();
+        let matching = inner
+            .live_collections()
+            .filter(|collection| {
+                collection
+                    .metadata
+                    .as_ref()
+                    .is_some_and(|metadata| metadata.get(&key) == Some(&value))
+            })
+            .map(|collection| collection.collection_id)
+            .collect::<Vec<_>>();
+        let now = inner.now;
+        for collection_id in &matching {
+            inner.deleted_collections.insert(*collection_id, now);
+            *inner.seqnos.entry(*collection_id).or_insert(0) += 1;
+            inner.notify(*collection_id, SysDbEventKind::Deleted);
+        }
+        matching.len()
+    }
+
+    /// Soft-deletes a collection. It stays stored but is hidden from reads that
+    /// don't ask for deleted collections.
+    pub async fn delete_collection(
+        &mut self,
+        collection_id: CollectionUuid,
+    ) -> Result<(), DeleteCollectionError> {
+        let mut inner = self.inner.lock();
+        if inner.live_collection(&collection_id).is_none() {
+            return Err(DeleteCollectionError::NotFound(collection_id.to_string()));
+        }
+        let now = inner.now;
+        inner.deleted_collections.insert(collection_id, now);
+        *inner.seqnos.entry(collection_id).or_insert(0) += 1;
+        inner.notify(collection_id, SysDbEventKind::Deleted);
+        Ok(())
+    }
+
+    /// Brings back a soft-deleted collection as it was when deleted.
+    pub fn undelete_collection(
+        &mut self,
+        collection_id: CollectionUuid,
+    ) -> Result<(), DeleteCollectionError> {
+        let mut inner = self.inner.lock();
+        if inner.deleted_collections.remove(&collection_id).is_none() {
+            return Err(DeleteCollectionError::NotFound(collection_id.to_string()));
+        }
+        *inner.seqnos.entry(collection_id).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /// Collections soft-deleted before `cutoff_time`, ordered by id.
+    pub fn list_soft_deleted_collections(&self, cutoff_time: i64) -> Vec<Collection> {
+        let inner = self.inner.lock();
+        let mut collections = inner
+            .deleted_collections
+            .iter()
+            .filter(|(_, deleted_at)| **deleted_at < cutoff_time)
+            .filter_map(|(collection_id, _)| inner.collections.get(collection_id).cloned())
+            .collect::<Vec<_>>();
+        collections.sort_by_key(|collection| collection.collection_id);
+        collections
+    }
+
+    /// Removes a soft-deleted collection for good, along with its segments and
+    /// version history.
+    pub fn purge_collection(
+        &mut self,
+        collection_id: CollectionUuid,
+    ) -> Result<(), DeleteCollectionError> {
+        let mut inner = self.inner.lock();
+        if inner.deleted_collections.remove(&collection_id).is_none() {
+            return Err(DeleteCollectionError::NotFound(collection_id.to_string()));
+        }
+        inner.collections.remove(&collection_id);
+        let segment_ids = inner
+            .segments
+            .values()
+            .filter(|segment| segment.collection == collection_id)
+            .map(|segment| segment.id)
+            .collect::<Vec<_>>();
+        for segment_id in segment_ids {
+            inner.segments.remove(&segment_id);
+            inner.segment_updated_at.remove(&segment_id);
+        }
+        inner.versions.remove(&collection_id);
+        inner.version_files.remove(&collection_id);
+        *inner.seqnos.entry(collection_id).or_insert(0) += 1;
+        Ok(())
+    }
+
+    pub fn set_now(&mut self, now: i64) {
+        let mut inner = self.inner.lock();
+        inner.now = now;
+        inner.call_log.push(SysDbCall::SetNow(now));
+    }
+
+    /// Current sequence number of every collection.
+    pub fn snapshot(&self) -> SysDbSnapshot {
+        let inner = self.inner.lock();
+        SysDbSnapshot {
+            seqnos: inner.seqnos.clone(),
+        }
+    }
+
+    /// Receives an event for every collection created, flushed, or deleted
+    /// from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<SysDbEvent> {
+        self.inner.lock().events.0.subscribe()
+    }
+
+    /// Drains the flushes that succeeded since the last call, oldest first.
+    pub fn take_compaction_log(&mut self) -> Vec<CompactionRecord> {
+        let mut inner = self.inner.lock();
+        std::mem::take(&mut inner.compaction_log)
+    }
+
+    /// Every call made so far, oldest first.
+    pub fn call_log(&self) -> Vec<SysDbCall> {
+        let inner = self.inner.lock();
+        inner.call_log.clone()
+    }
+
+    /// Applies the mutating calls of a recorded log in order, skipping reads.
+    /// Stops at the first call that fails.
+    pub async fn replay(&mut self, calls: Vec<SysDbCall>) -> Result<(), ReplayError> {
+        for (index, call) in calls.into_iter().enumerate() {
+            match call {
+                SysDbCall::AddCollection(collection) => self.add_collection(collection),
+                SysDbCall::AddSegment(segment) => self.add_segment(segment),
+                SysDbCall::CreateDatabase {
+                    database_id,
+                    name,
+                    tenant,
+                } => {
+                    self.create_database(database_id, name, tenant)
+                        .await
+                        .map_err(|source| ReplayError::CreateDatabase { index, source })?;
+                }
+                SysDbCall::SetNow(now) => self.set_now(now),
+                SysDbCall::FlushCompaction(request) => {
+                    self.flush(request)
+                        .await
+                        .map_err(|source| ReplayError::Flush { index, source })?;
+                }
+                SysDbCall::GetCollections(_)
+                | SysDbCall::GetSegments { .. }
+                | SysDbCall::ListDatabases { .. } => {}
             }
-            let mut segment = segment.unwrap().clone();
-            segment.file_path = segment_flush_info.file_paths.clone();
+        }
+        Ok(())
+    }
+
+    /// Installs a check run before every flush. A flush it rejects fails with
+    /// `FlushCompactionError::ValidationFailed` and changes nothing.
+    pub fn set_flush_validator(&mut self, f: Arc<FlushValidatorFn>) {
+        let mut inner = self.inner.lock();
+        inner.flush_validator = Some(FlushValidator(f));
+    }
+
+    /// Removes and adds segments of a collection as one change. Nothing is
+    /// applied unless every removal exists and every addition belongs to the
+    /// collection, including additions whose id is already taken by a segment
+    /// of another collection.
+    pub fn apply_segment_delta(
+        &mut self,
+        collection_id: CollectionUuid,
+        remove: Vec<SegmentUuid>,
+        add: Vec<Segment>,
+    ) -> Result<(), SegmentDeltaError> {
+        let mut inner = self.inner.lock();
+        if inner.live_collection(&collection_id).is_none() {
+            return Err(SegmentDeltaError::CollectionNotFound(collection_id));
+        }
+        for segment_id in &remove {
+            match inner.segments.get(segment_id) {
+                Some(segment) if segment.collection == collection_id => {}
+                _ => return Err(SegmentDeltaError::SegmentNotFound(*segment_id)),
+            }
+        }
+        if let Some(segment) = add.iter().find(|segment| {
+            segment.collection != collection_id
+                || inner
+                    .segments
+                    .get(&segment.id)
+                    .is_some_and(|existing| existing.collection != collection_id)
+        }) {
+            return Err(SegmentDeltaError::WrongCollection(segment.id));
+        }
+
+        let changed = !remove.is_empty() || !add.is_empty();
+        for segment_id in remove {
+            inner.segments.remove(&segment_id);
+            inner.segment_updated_at.remove(&segment_id);
+        }
+        let now = inner.now;
+        for segment in add {
+            inner.segment_updated_at.insert(segment.id, now);
             inner.segments.insert(segment.id, segment);
         }
+        if changed {
+            *inner.seqnos.entry(collection_id).or_insert(0) += 1;
+        }
+        Ok(())
+    }
 
-        Ok(FlushCompactionResponse::new(
-            collection_id,
-            new_collection_version,
-            last_compaction_time,
-        ))
+    /// Records that the log of `collection_id` reached `log_position` at `timestamp`.
+    pub fn append_log(&mut self, collection_id: CollectionUuid, log_position: i64, timestamp: i64) {
+        let mut inner = self.inner.lock();
+        inner
+            .log_writes
+            .entry(collection_id)
+            .or_default()
+            .push((log_position, timestamp));
+    }
+
+    /// Advances the collection's version by one if it is still `expected_version`.
+    pub fn cas_bump_version(
+        &mut self,
+        collection_id: CollectionUuid,
+        expected_version: i32,
+    ) -> Result<i32, CasError> {
+        let mut inner = self.inner.lock();
+        if inner.live_collection(&collection_id).is_none() {
+            return Err(CasError::NotFound(collection_id));
+        }
+        let collection = inner
+            .collections
+            .get_mut(&collection_id)
+            .ok_or(CasError::NotFound(collection_id))?;
+        if collection.version != expected_version {
+            return Err(CasError::Mismatch {
+                expected: expected_version,
+                actual: collection.version,
+            });
+        }
+        collection.version += 1;
+        let version = collection.version;
+        *inner.seqnos.entry(collection_id).or_insert(0) += 1;
+        Ok(version)
+    }
+
+    /// Replaces the collection with the one in `snapshot` as a single change,
+    /// so no reader sees it between dropping the old segments and adding the
+    /// new ones.
+    pub fn restore_from_snapshot(
+        &mut self,
+        snapshot: CollectionSnapshot,
+    ) -> Result<(), RestoreError> {
+        let CollectionSnapshot {
+            collection,
+            segments,
+            versions,
+            overwrite,
+        } = snapshot;
+        let collection_id = collection.collection_id;
+        let mut inner = self.inner.lock();
+        if inner.collections.contains_key(&collection_id) {
+            if !overwrite {
+                return Err(RestoreError::AlreadyExists(collection_id));
+            }
+            let replaced = inner
+                .segments
+                .values()
+                .filter(|segment| segment.collection == collection_id)
+                .map(|segment| segment.id)
+                .collect::<Vec<_>>();
+            for segment_id in &replaced {
+                inner.segments.remove(segment_id);
+                inner.segment_updated_at.remove(segment_id);
+            }
+            inner.deleted_collections.remove(&collection_id);
+            *inner.seqnos.entry(collection_id).or_insert(0) += 1;
+        }
+        inner.versions.insert(collection_id, versions);
+        inner.insert_collection(collection);
+        for segment in segments {
+            inner.insert_segment(segment);
+        }
+        Ok(())
+    }
+
+    pub fn set_compaction_cooldown(&mut self, cooldown: Duration) {
+        let mut inner = self.inner.lock();
+        inner.compaction_cooldown = cooldown;
+    }
+
+    pub fn set_version_file(&mut self, collection_id: CollectionUuid, path: String, version: i32) {
+        let mut inner = self.inner.lock();
+        inner.version_files.insert(collection_id, (path, version));
+    }
+
+    pub fn set_flush_pause(&mut self, duration: Duration) {
+        let mut inner = self.inner.lock();
+        inner.flush_pause = Some(duration);
+    }
+
+    pub fn set_segment_record_count(&mut self, segment_id: SegmentUuid, count: u64) {
+        let mut inner = self.inner.lock();
+        inner.segment_record_counts.insert(segment_id, count);
+    }
+
+    pub fn set_available_space(&mut self, bytes: u64) {
+        let mut inner = self.inner.lock();
+        inner.available_space = Some(bytes);
+    }
+
+    /// Caps the versions kept per collection. Flushes drop the oldest versions
+    /// beyond the cap and record them as auto-pruned. A cap of 0 is treated as
+    /// 1 so the current version is never pruned.
+    pub fn set_max_version_history(&mut self, n: usize) {
+        let mut inner = self.inner.lock();
+        inner.max_version_history = Some(n.max(1));
+    }
+
+    pub fn set_segment_read_failure(&mut self, enabled: bool) {
+        let mut inner = self.inner.lock();
+        inner.segment_read_failure = enabled;
+    }
+
+    pub fn next_epoch(&mut self) -> i64 {
+        let mut inner = self.inner.lock();
+        inner.epoch += 1;
+        inner.epoch
+    }
+
+    pub fn protect_version(&mut self, collection_id: CollectionUuid, version: i32) {
+        let mut inner = self.inner.lock();
+        inner
+            .protected_versions
+            .entry(collection_id)
+            .or_default()
+            .insert(version);
+    }
+
+    pub fn set_tenant_flag(&mut self, tenant: String, flag: String) {
+        let mut inner = self.inner.lock();
+        inner.tenant_flags.entry(tenant).or_default().insert(flag);
+    }
+
+    pub fn has_tenant_flag(&self, tenant: String, flag: String) -> bool {
+        let inner = self.inner.lock();
+        inner
+            .tenant_flags
+            .get(&tenant)
+            .is_some_and(|flags| flags.contains(&flag))
+    }
+
+    pub fn add_tenant_last_compaction_time(&mut self, tenant: String, last_compaction_time: i64) {
+        let mut inner = self.inner.lock();
+        inner
+            .tenant_last_compaction_time
+            .insert(tenant, last_compaction_time);
+    }
+
+    pub fn grant_access(&mut self, principal: String, tenant: String, database: String) {
+        let mut inner = self.inner.lock();
+        inner
+            .grants
+            .entry(principal)
+            .or_default()
+            .insert(format!("{}:{}", tenant, database));
+    }
+
+    pub fn revoke_access(&mut self, principal: String, tenant: String, database: String) {
+        let mut inner = self.inner.lock();
+        if let Some(grants) = inner.grants.get_mut(&principal) {
+            grants.remove(&format!("{}:{}", tenant, database));
+        }
+    }
+
+    pub fn accessible_databases(&self, principal: String) -> Vec<Database> {
+        let inner = self.inner.lock();
+        let grants = match inner.grants.get(&principal) {
+            Some(grants) => grants,
+            None => return Vec::new(),
+        };
+        let mut granted = grants
+            .iter()
+            .filter_map(|grant| grant.split_once(':'))
+            .collect::<Vec<_>>();
+        granted.sort();
+
+        granted
+            .into_iter()
+            .filter_map(|(tenant, database)| inner.database_named(tenant, database).cloned())
+            .collect()
+    }
+
+    pub fn list_collections_by_creation_order(&self, tenant: Option<String>) -> Vec<Collection> {
+        let filter = CollectionFilter {
+            tenant,
+            ..Default::default()
+        };
+        let inner = self.inner.lock();
+        let mut collections = inner
+            .live_collections()
+            .filter(|collection| TestSysDb::filter_collections(collection, &filter))
+            .cloned()
+            .collect::<Vec<_>>();
+        collections
+            .sort_by_key(|collection| inner.creation_seq.get(&collection.collection_id).copied());
+        collections
     }
 
-    pub(crate) async fn mark_version_for_deletion(
+    pub fn get_collection_at_version(
         &self,
-        _epoch_id: i64,
-        versions: Vec<VersionListForCollection>,
-    ) -> Result<(), String> {
-        // For testing success case, return Ok when versions are not empty
-        if !versions.is_empty() && !versions[0].versions.is_empty() {
-            // Simulate error case when version is 1
-            if versions[0].versions.contains(&1) {
-                return Err("Failed to mark version for deletion".to_string());
+        collection_id: CollectionUuid,
+        version: i32,
+    ) -> Result<Collection, GetCollectionsError> {
+        let inner = self.inner.lock();
+        if inner.live_collection(&collection_id).is_none() {
+            return Err(GetCollectionsError::NotFound(collection_id.to_string()));
+        }
+        inner
+            .versions
+            .get(&collection_id)
+            .and_then(|versions| versions.iter().find(|record| record.version == version))
+            .map(|record| record.collection.clone())
+            .ok_or(GetCollectionsError::VersionNotFound(version))
+    }
+
+    /// Returns segments updated strictly after `since`, ordered by id.
+    pub fn segments_updated_since(&self, since: i64) -> Vec<Segment> {
+        let inner = self.inner.lock();
+        let mut segments = inner
+            .segments
+            .values()
+            .filter(|segment| {
+                inner
+                    .segment_updated_at
+                    .get(&segment.id)
+                    .is_some_and(|updated_at| *updated_at > since)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        segments.sort_by_key(|segment| segment.id);
+        segments
+    }
+
+    pub fn auto_pruned_versions(&self, collection_id: CollectionUuid) -> Vec<i32> {
+        let inner = self.inner.lock();
+        inner
+            .auto_pruned_versions
+            .get(&collection_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn database_dimension(
+        &self,
+        tenant: String,
+        database: String,
+    ) -> Result<Option<u32>, DatabaseDimensionError> {
+        let inner = self.inner.lock();
+        let mut dimension = None;
+        for collection in inner.live_collections() {
+            if collection.tenant != tenant || collection.database != database {
+                continue;
+            }
+            let Some(collection_dimension) = collection.dimension else {
+                continue;
+            };
+            let collection_dimension = collection_dimension as u32;
+            match dimension {
+                Some(dimension) if dimension != collection_dimension => {
+                    return Err(DatabaseDimensionError::Mismatch);
+                }
+                _ => dimension = Some(collection_dimension),
             }
-            Ok(())
-        } else {
-            Ok(())
         }
+        Ok(dimension)
     }
 
-    pub async fn delete_collection_version(
+    pub fn collections_with_pending_flushes(&self) -> Vec<CollectionUuid> {
+        let inner = self.inner.lock();
+        let mut collection_ids = inner
+            .pending_flushes
+            .values()
+            .map(|request| request.collection_id)
+            .collect::<Vec<_>>();
+        collection_ids.sort();
+        collection_ids.dedup();
+        collection_ids
+    }
+
+    /// Returns the change in record count of every collection whose size moved
+    /// since the previous call, then makes the current sizes the new baseline.
+    /// Collections never observed before are compared against zero.
+    pub fn collections_with_size_change(&mut self) -> Vec<(CollectionUuid, i64)> {
+        let mut inner = self.inner.lock();
+        let sizes = inner
+            .live_collections()
+            .map(|collection| {
+                (
+                    collection.collection_id,
+                    collection.total_records_post_compaction,
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut changes = Vec::new();
+        for (collection_id, size) in sizes {
+            let previous = inner
+                .last_observed_size
+                .insert(collection_id, size)
+                .unwrap_or(0);
+            if previous != size {
+                changes.push((collection_id, size as i64 - previous as i64));
+            }
+        }
+        changes.sort_by_key(|(collection_id, _)| *collection_id);
+        changes
+    }
+
+    /// Returns tenants, known either through their collections or a compaction
+    /// time, that have not compacted since `cutoff`.
+    pub fn idle_tenants(&self, cutoff: i64) -> Vec<String> {
+        let inner = self.inner.lock();
+        let mut tenants = inner
+            .live_collections()
+            .map(|collection| collection.tenant.clone())
+            .chain(inner.tenant_last_compaction_time.keys().cloned())
+            .collect::<Vec<_>>();
+        tenants.sort();
+        tenants.dedup();
+        tenants.retain(
+            |tenant| match inner.tenant_last_compaction_time.get(tenant) {
+                Some(last_compaction_time) => *last_compaction_time < cutoff,
+                None => true,
+            },
+        );
+        tenants
+    }
+
+    pub fn resolve_collection_full(
         &self,
-        _versions: Vec<VersionListForCollection>,
-    ) -> HashMap<String, bool> {
-        // For testing, return success for all collections
-        let mut results = HashMap::new();
-        for version_list in _versions {
-            results.insert(version_list.collection_id, true);
+        tenant: String,
+        database: String,
+        name: String,
+    ) -> Result<(Collection, Database), ResolveError> {
+        let inner = self.inner.lock();
+        let Some(database) = inner.database_named(&tenant, &database) else {
+            return Err(ResolveError::DatabaseNotFound(database));
+        };
+        let collection = inner
+            .live_collections()
+            .find(|collection| {
+                collection.tenant == tenant
+                    && collection.database == database.name
+                    && collection.name == name
+            })
+            .cloned()
+            .ok_or(ResolveError::CollectionNotFound(name))?;
+        let database = database.clone();
+        Ok((collection, database))
+    }
+
+    /// Flushes per second for `tenant` over the `window_secs` ending at `now`.
+    pub fn compaction_rate(&self, tenant: String, window_secs: i64, now: i64) -> f64 {
+        if window_secs <= 0 {
+            return 0.0;
         }
-        results
+        let inner = self.inner.lock();
+        let flushes = inner
+            .tenant_flush_times
+            .get(&tenant)
+            .map(|flush_times| {
+                flush_times
+                    .iter()
+                    .filter(|flush_time| **flush_time > now - window_secs && **flush_time <= now)
+                    .count()
+            })
+            .unwrap_or(0);
+        flushes as f64 / window_secs as f64
     }
 
-    pub(crate) async fn get_collection_size(
+    pub fn collection_topology(
         &self,
         collection_id: CollectionUuid,
-    ) -> Result<usize, GetCollectionSizeError> {
+    ) -> Result<CollectionTopology, GetCollectionsError> {
         let inner = self.inner.lock();
-        let collection = inner.collections.get(&collection_id);
-        match collection {
-            Some(collection) => Ok(collection.total_records_post_compaction as usize),
-            None => Err(GetCollectionSizeError::NotFound(
-                "Collection not found".to_string(),
-            )),
+        let collection = inner
+            .live_collection(&collection_id)
+            .cloned()
+            .ok_or(GetCollectionsError::NotFound(collection_id.to_string()))?;
+        let mut segments = inner
+            .segments
+            .values()
+            .filter(|segment| segment.collection == collection_id)
+            .map(|segment| {
+                let file_count = segment.file_path.values().map(|paths| paths.len()).sum();
+                (segment.scope.clone(), segment.id, file_count)
+            })
+            .collect::<Vec<_>>();
+        segments.sort();
+        Ok(CollectionTopology {
+            collection,
+            segments,
+        })
+    }
+
+    /// A live collection and its segments, ordered by id, read under one lock.
+    pub fn get_collection_with_segments(
+        &self,
+        collection_id: CollectionUuid,
+    ) -> Result<(Collection, Vec<Segment>), GetCollectionsError> {
+        let inner = self.inner.lock();
+        let collection = match inner.live_collection(&collection_id) {
+            Some(collection) => collection.clone(),
+            None => return Err(GetCollectionsError::NotFound(collection_id.to_string())),
+        };
+        let mut segments = inner
+            .segments
+            .values()
+            .filter(|segment| segment.collection == collection_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        segments.sort_by_key(|segment| segment.id);
+        Ok((collection, segments))
+    }
+
+    pub fn tenants_over_collection_limit(&self, limit: usize) -> Vec<String> {
+        let inner = self.inner.lock();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for collection in inner.live_collections() {
+            *counts.entry(collection.tenant.clone()).or_insert(0) += 1;
         }
+        let mut tenants = counts
+            .into_iter()
+            .filter(|(_, count)| *count > limit)
+            .map(|(tenant, _)| tenant)
+            .collect::<Vec<_>>();
+        tenants.sort();
+        tenants
+    }
+
+    /// Two collections are compatible when their dimensions agree and their
+    /// configurations are equal. An unset dimension or null configuration
+    /// matches anything.
+    pub fn collections_compatible(
+        &self,
+        a: CollectionUuid,
+        b: CollectionUuid,
+    ) -> Result<bool, GetCollectionsError> {
+        let inner = self.inner.lock();
+        let a = inner
+            .live_collection(&a)
+            .ok_or(GetCollectionsError::NotFound(a.to_string()))?;
+        let b = inner
+            .live_collection(&b)
+            .ok_or(GetCollectionsError::NotFound(b.to_string()))?;
+        let dimension_compatible = match (a.dimension, b.dimension) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        };
+        let configuration_compatible = a.configuration_json.is_null()
+            || b.configuration_json.is_null()
+            || a.configuration_json == b.configuration_json;
+        Ok(dimension_compatible && configuration_compatible)
+    }
+
+    pub fn most_recently_compacted(&self, tenant: String) -> Option<CollectionUuid> {
+        let inner = self.inner.lock();
+        inner
+            .live_collections()
+            .filter(|collection| collection.tenant == tenant)
+            .filter_map(|collection| {
+                inner
+                    .last_compaction_at
+                    .get(&collection.collection_id)
+                    .map(|compacted_at| (*compacted_at, collection.collection_id))
+            })
+            .max()
+            .map(|(_, collection_id)| collection_id)
+    }
+
+    /// Plans a GC pass. A version is deletable unless it is one of the newest
+    /// `min_versions_to_keep`, was created at or after `cutoff_time`, or is
+    /// protected. The collection's current version is always kept, even when
+    /// `min_versions_to_keep` is 0.
+    pub fn gc_plan(&self, min_versions_to_keep: usize, cutoff_time: i64) -> GcPlan {
+        let min_versions_to_keep = min_versions_to_keep.max(1);
+        let inner = self.inner.lock();
+        let mut collections = Vec::new();
+        for (collection_id, versions) in inner.versions.iter() {
+            let protected = inner.protected_versions.get(collection_id);
+            let (deletable, kept): (Vec<_>, Vec<_>) =
+                versions.iter().enumerate().partition(|(index, record)| {
+                    versions.len() - index > min_versions_to_keep
+                        && record.created_at < cutoff_time
+                        && !protected.is_some_and(|protected| protected.contains(&record.version))
+                });
+            if deletable.is_empty() {
+                continue;
+            }
+
+            let version_paths = |records: &[(usize, &VersionRecord)]| {
+                records
+                    .iter()
+                    .flat_map(|(_, record)| record.segments.iter())
+                    .flat_map(|segment| segment.file_path.values().flatten().cloned())
+                    .collect::<HashSet<_>>()
+            };
+            let mut referenced = version_paths(&kept);
+            referenced.extend(
+                inner
+                    .segments
+                    .values()
+                    .filter(|segment| segment.collection == *collection_id)
+                    .flat_map(|segment| segment.file_path.values().flatten().cloned()),
+            );
+            let mut unreferenced_paths = version_paths(&deletable)
+                .difference(&referenced)
+                .cloned()
+                .collect::<Vec<_>>();
+            unreferenced_paths.sort();
+
+            collections.push(CollectionGcPlan {
+                collection_id: *collection_id,
+                versions: deletable.iter().map(|(_, record)| record.version).collect(),
+                unreferenced_paths,
+            });
+        }
+        collections.sort_by_key(|plan| plan.collection_id);
+        GcPlan { collections }
+    }
+
+    pub fn collections_by_version(
+        &self,
+        tenant: Option<String>,
+    ) -> BTreeMap<i32, Vec<CollectionUuid>> {
+        let filter = CollectionFilter {
+            tenant,
+            ..Default::default()
+        };
+        let inner = self.inner.lock();
+        let mut by_version: BTreeMap<i32, Vec<CollectionUuid>> = BTreeMap::new();
+        for collection in inner.live_collections() {
+            if !TestSysDb::filter_collections(collection, &filter) {
+                continue;
+            }
+            by_version
+                .entry(collection.version)
+                .or_default()
+                .push(collection.collection_id);
+        }
+        for collection_ids in by_version.values_mut() {
+            collection_ids.sort();
+        }
+        by_version
+    }
+
+    pub fn segments_at_version(
+        &self,
+        collection_id: CollectionUuid,
+        version: i32,
+    ) -> Result<Vec<Segment>, GetSegmentsError> {
+        let inner = self.inner.lock();
+        let mut segments = inner
+            .versions
+            .get(&collection_id)
+            .and_then(|versions| versions.iter().find(|record| record.version == version))
+            .map(|record| record.segments.clone())
+            .ok_or(GetSegmentsError::VersionNotFound(version))?;
+        segments.sort_by_key(|segment| segment.id);
+        Ok(segments)
+    }
+
+    /// The file paths `segment_id` had right after the flush that produced
+    /// `version`. Gone once that version is deleted or pruned.
+    pub fn get_segment_file_paths_at_version(
+        &self,
+        segment_id: SegmentUuid,
+        version: i32,
+    ) -> Option<HashMap<String, Vec<String>>> {
+        let inner = self.inner.lock();
+        inner
+            .versions
+            .values()
+            .flatten()
+            .filter(|record| record.version == version)
+            .flat_map(|record| record.segments.iter())
+            .find(|segment| segment.id == segment_id)
+            .map(|segment| segment.file_path.clone())
+    }
+
+    /// Returns the files referenced by the plan's versions and by no version
+    /// outside the plan, nor by the collection's live segments.
+    pub fn deletable_paths(&self, plan: &GcPlan) -> Vec<String> {
+        let inner = self.inner.lock();
+        let mut deletable = HashSet::new();
+        for collection_plan in &plan.collections {
+            let Some(versions) = inner.versions.get(&collection_plan.collection_id) else {
+                continue;
+            };
+            let mut planned = HashSet::new();
+            let mut surviving = HashSet::new();
+            for record in versions {
+                let paths = if collection_plan.versions.contains(&record.version) {
+                    &mut planned
+                } else {
+                    &mut surviving
+                };
+                paths.extend(
+                    record
+                        .segments
+                        .iter()
+                        .flat_map(|segment| segment.file_path.values().flatten().cloned()),
+                );
+            }
+            surviving.extend(
+                inner
+                    .segments
+                    .values()
+                    .filter(|segment| segment.collection == collection_plan.collection_id)
+                    .flat_map(|segment| segment.file_path.values().flatten().cloned()),
+            );
+            deletable.extend(planned.difference(&surviving).cloned());
+        }
+        let mut deletable = deletable.into_iter().collect::<Vec<_>>();
+        deletable.sort();
+        deletable
+    }
+
+    pub fn pending_first_compaction(&self, tenant: String) -> Vec<CollectionUuid> {
+        let inner = self.inner.lock();
+        let mut collection_ids = inner
+            .live_collections()
+            .filter(|collection| collection.tenant == tenant && collection.version == 0)
+            .map(|collection| collection.collection_id)
+            .collect::<Vec<_>>();
+        collection_ids.sort();
+        collection_ids
+    }
+
+    /// Age at `now` of the oldest log write not yet covered by a compaction,
+    /// across the tenant's collections. None when everything is compacted.
+    pub fn oldest_uncompacted_age(&self, tenant: String, now: i64) -> Option<i64> {
+        let inner = self.inner.lock();
+        inner
+            .live_collections()
+            .filter(|collection| collection.tenant == tenant)
+            .filter_map(|collection| {
+                inner
+                    .log_writes
+                    .get(&collection.collection_id)?
+                    .iter()
+                    .filter(|(log_position, _)| *log_position > collection.log_position)
+                    .map(|(_, timestamp)| now - timestamp)
+                    .max()
+            })
+            .max()
+    }
+
+    pub fn flushes_for_job(&self, job_id: String) -> Vec<(CollectionUuid, i32)> {
+        let inner = self.inner.lock();
+        let mut flushes = inner
+            .versions
+            .iter()
+            .flat_map(|(collection_id, versions)| {
+                versions
+                    .iter()
+                    .filter(|record| record.job_id.as_ref() == Some(&job_id))
+                    .map(|record| (*collection_id, record.version))
+            })
+            .collect::<Vec<_>>();
+        flushes.sort();
+        flushes
+    }
+
+    /// Returns the collection's current segment files that would no longer be
+    /// referenced once `flush_info` is applied.
+    pub fn flush_orphaned_paths(
+        &self,
+        collection_id: CollectionUuid,
+        flush_info: &[SegmentFlushInfo],
+    ) -> Result<Vec<String>, GetSegmentsError> {
+        let inner = self.inner.lock();
+        if inner.segment_read_failure {
+            return Err(GetSegmentsError::Unavailable);
+        }
+        let segments = inner
+            .segments
+            .values()
+            .filter(|segment| segment.collection == collection_id)
+            .collect::<Vec<_>>();
+        let mut after = flush_info
+            .iter()
+            .flat_map(|info| info.file_paths.values().flatten())
+            .collect::<HashSet<_>>();
+        after.extend(
+            segments
+                .iter()
+                .filter(|segment| !flush_info.iter().any(|info| info.segment_id == segment.id))
+                .flat_map(|segment| segment.file_path.values().flatten()),
+        );
+        let mut orphaned = segments
+            .iter()
+            .flat_map(|segment| segment.file_path.values().flatten())
+            .filter(|path| !after.contains(path))
+            .cloned()
+            .collect::<Vec<_>>();
+        orphaned.sort();
+        orphaned.dedup();
+        Ok(orphaned)
+    }
+
+    pub fn collections_with_tenant_compaction_time(
+        &self,
+        tenant: String,
+    ) -> Vec<(Collection, i64)> {
+        let inner = self.inner.lock();
+        let last_compaction_time = inner
+            .tenant_last_compaction_time
+            .get(&tenant)
+            .copied()
+            .unwrap_or(0);
+        let mut collections = inner
+            .live_collections()
+            .filter(|collection| collection.tenant == tenant)
+            .map(|collection| (collection.clone(), last_compaction_time))
+            .collect::<Vec<_>>();
+        collections.sort_by_key(|(collection, _)| collection.collection_id);
+        collections
+    }
+
+    pub fn fragmented_collections(&self, max_files_per_segment: usize) -> Vec<CollectionUuid> {
+        let inner = self.inner.lock();
+        let mut collection_ids = inner
+            .segments
+            .values()
+            .filter(|segment| {
+                segment
+                    .file_path
+                    .values()
+                    .map(|paths| paths.len())
+                    .sum::<usize>()
+                    > max_files_per_segment
+            })
+            .map(|segment| segment.collection)
+            .filter(|collection_id| inner.live_collection(collection_id).is_some())
+            .collect::<Vec<_>>();
+        collection_ids.sort();
+        collection_ids.dedup();
+        collection_ids
+    }
+
+    pub fn segment_path_diff(
+        &self,
+        a: CollectionUuid,
+        b: CollectionUuid,
+    ) -> Result<SegmentPathDiff, GetCollectionsError> {
+        let inner = self.inner.lock();
+        let paths = |collection_id: CollectionUuid| {
+            if inner.live_collection(&collection_id).is_none() {
+                return Err(GetCollectionsError::NotFound(collection_id.to_string()));
+            }
+            Ok(inner
+                .segments
+                .values()
+                .filter(|segment| segment.collection == collection_id)
+                .flat_map(|segment| segment.file_path.values().flatten().cloned())
+                .collect::<HashSet<_>>())
+        };
+        let a = paths(a)?;
+        let b = paths(b)?;
+        let sorted = |paths: HashSet<&String>| {
+            let mut paths = paths.into_iter().cloned().collect::<Vec<_>>();
+            paths.sort();
+            paths
+        };
+        Ok(SegmentPathDiff {
+            only_a: sorted(a.difference(&b).collect()),
+            only_b: sorted(b.difference(&a).collect()),
+            shared: sorted(a.intersection(&b).collect()),
+        })
+    }
+
+    pub fn total_versions(&self) -> usize {
+        let inner = self.inner.lock();
+        inner.versions.values().fold(0usize, |total, versions| {
+            total.saturating_add(versions.len())
+        })
+    }
+
+    pub fn collections_sharing_paths(&self) -> Vec<(String, Vec<CollectionUuid>)> {
+        let inner = self.inner.lock();
+        let mut owners: BTreeMap<String, Vec<CollectionUuid>> = BTreeMap::new();
+        for segment in inner.segments.values() {
+            for path in segment.file_path.values().flatten() {
+                owners
+                    .entry(path.clone())
+                    .or_default()
+                    .push(segment.collection);
+            }
+        }
+        owners
+            .into_iter()
+            .filter_map(|(path, mut collection_ids)| {
+                collection_ids.sort();
+                collection_ids.dedup();
+                (collection_ids.len() > 1).then_some((path, collection_ids))
+            })
+            .collect()
+    }
+
+    /// Picks the collection that has gone longest without compacting, with
+    /// never-compacted collections first. Collections still in their
+    /// compaction cooldown are skipped.
+    pub fn next_compaction_candidate(&self) -> Option<CollectionUuid> {
+        let inner = self.inner.lock();
+        let cooldown = inner.compaction_cooldown.as_secs() as i64;
+        inner
+            .live_collections()
+            .filter_map(|collection| {
+                let last_compaction_at = inner.last_compaction_at.get(&collection.collection_id);
+                match last_compaction_at {
+                    Some(compacted_at) if inner.now - compacted_at < cooldown => None,
+                    _ => Some((last_compaction_at.copied(), collection.collection_id)),
+                }
+            })
+            .min()
+            .map(|(_, collection_id)| collection_id)
+    }
+
+    /// Returns collections whose version file was written at an older version
+    /// than the collection's current one. A missing version file counts as
+    /// written at version 0.
+    pub fn collections_with_stale_version_file(&self) -> Vec<CollectionUuid> {
+        let inner = self.inner.lock();
+        let mut collection_ids = inner
+            .live_collections()
+            .filter(|collection| {
+                let stamp = inner
+                    .version_files
+                    .get(&collection.collection_id)
+                    .map(|(_, version)| *version)
+                    .unwrap_or(0);
+                stamp < collection.version
+            })
+            .map(|collection| collection.collection_id)
+            .collect::<Vec<_>>();
+        collection_ids.sort();
+        collection_ids
+    }
+
+    pub fn full_manifest(&self, tenant: Option<String>) -> Vec<CollectionManifest> {
+        let filter = CollectionFilter {
+            tenant,
+            ..Default::default()
+        };
+        let inner = self.inner.lock();
+        let mut manifests = inner
+            .live_collections()
+            .filter(|collection| TestSysDb::filter_collections(collection, &filter))
+            .map(|collection| CollectionManifest {
+                collection_id: collection.collection_id,
+                version: collection.version,
+                segments: inner
+                    .segments
+                    .values()
+                    .filter(|segment| segment.collection == collection.collection_id)
+                    .map(|segment| (segment.id, segment.file_path.clone()))
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        manifests.sort_by_key(|manifest| manifest.collection_id);
+        manifests
+    }
+
+    pub fn collections_with_changed_tenant(&self) -> Vec<CollectionUuid> {
+        let inner = self.inner.lock();
+        let mut collection_ids = inner
+            .live_collections()
+            .filter(|collection| {
+                inner
+                    .original_tenants
+                    .get(&collection.collection_id)
+                    .is_some_and(|tenant| *tenant != collection.tenant)
+            })
+            .map(|collection| collection.collection_id)
+            .collect::<Vec<_>>();
+        collection_ids.sort();
+        collection_ids
+    }
+
+    pub fn aggregate_stats(&self) -> AggregateStats {
+        let inner = self.inner.lock();
+        let mut stats = AggregateStats::default();
+        let mut tenants = HashSet::new();
+        let mut databases = HashSet::new();
+        for collection in inner.live_collections() {
+            stats.total_collections += 1;
+            stats.total_records += collection.total_records_post_compaction;
+            stats.total_versions += inner
+                .versions
+                .get(&collection.collection_id)
+                .map_or(0, |versions| versions.len());
+            tenants.insert(collection.tenant.as_str());
+            databases.insert((collection.tenant.as_str(), collection.database.as_str()));
+        }
+        stats.total_segments = inner
+            .segments
+            .values()
+            .filter(|segment| inner.live_collection(&segment.collection).is_some())
+            .count();
+        stats.distinct_tenants = tenants.len();
+        stats.distinct_databases = databases.len();
+        stats
+    }
+
+    /// Returns the visible collections matching every predicate in `filter`.
+    pub fn find_collections(&self, filter: &CollectionFilter) -> Vec<Collection> {
+        let inner = self.inner.lock();
+        let collections: Box<dyn Iterator<Item = &Collection>> = if filter.include_deleted {
+            Box::new(inner.collections.values())
+        } else {
+            Box::new(inner.live_collections())
+        };
+        collections
+            .filter(|collection| TestSysDb::filter_collections(collection, filter))
+            .cloned()
+            .collect()
+    }
+
+    pub fn segment_file_count(
+        &self,
+        collection_id: CollectionUuid,
+    ) -> Result<usize, GetCollectionsError> {
+        let inner = self.inner.lock();
+        if inner.live_collection(&collection_id).is_none() {
+            return Err(GetCollectionsError::NotFound(collection_id.to_string()));
+        }
+        Ok(inner
+            .segments
+            .values()
+            .filter(|segment| segment.collection == collection_id)
+            .flat_map(|segment| segment.file_path.values())
+            .map(|paths| paths.len())
+            .sum())
+    }
+
+    /// Read-only: a collection whose database no longer exists is reported
+    /// without one rather than having the database recreated.
+    pub fn tenant_snapshot(&self, tenant: String) -> TenantSnapshot {
+        let inner = self.inner.lock();
+        let mut collections = inner
+            .live_collections()
+            .filter(|collection| collection.tenant == tenant)
+            .cloned()
+            .collect::<Vec<_>>();
+        collections.sort_by_key(|collection| collection.collection_id);
+
+        let mut segments = inner
+            .segments
+            .values()
+            .filter(|segment| {
+                collections
+                    .iter()
+                    .any(|collection| collection.collection_id == segment.collection)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        segments.sort_by_key(|segment| segment.id);
+
+        let mut database_names = collections
+            .iter()
+            .map(|collection| collection.database.clone())
+            .collect::<Vec<_>>();
+        database_names.sort();
+        database_names.dedup();
+        let databases = database_names
+            .into_iter()
+            .filter_map(|name| inner.database_named(&tenant, &name).cloned())
+            .collect();
+
+        TenantSnapshot {
+            collections,
+            segments,
+            databases,
+            last_compaction_time: inner.tenant_last_compaction_time.get(&tenant).copied(),
+        }
+    }
+
+    pub fn total_flush_bytes(&self, collection_id: CollectionUuid) -> u64 {
+        let inner = self.inner.lock();
+        inner
+            .flush_bytes_written
+            .get(&collection_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn collections_with_missing_scopes(
+        &self,
+        required: Vec<SegmentScope>,
+    ) -> Vec<(CollectionUuid, Vec<SegmentScope>)> {
+        let inner = self.inner.lock();
+        let mut incomplete = Vec::new();
+        for collection in inner.live_collections() {
+            let missing = required
+                .iter()
+                .filter(|scope| {
+                    !inner.segments.values().any(|segment| {
+                        segment.collection == collection.collection_id && segment.scope == **scope
+                    })
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                incomplete.push((collection.collection_id, missing));
+            }
+        }
+        incomplete.sort_by_key(|(collection_id, _)| *collection_id);
+        incomplete
+    }
+
+    fn filter_collections(collection: &Collection, filter: &CollectionFilter) -> bool {
+        // Integer comparisons first, string comparisons last.
+        if filter
+            .version
+            .is_some_and(|version| version != collection.version)
+        {
+            return false;
+        }
+        if filter
+            .collection_id
+            .is_some_and(|collection_id| collection_id != collection.collection_id)
+        {
+            return false;
+        }
+        if filter
+            .tenant
+            .as_ref()
+            .is_some_and(|tenant| *tenant != collection.tenant)
+        {
+            return false;
+        }
+        if filter
+            .database
+            .as_ref()
+            .is_some_and(|database| *database != collection.database)
+        {
+            return false;
+        }
+        if filter
+            .name
+            .as_ref()
+            .is_some_and(|name| *name != collection.name)
+        {
+            return false;
+        }
+        if let Some(wanted) = &filter.metadata {
+            let Some(metadata) = &collection.metadata else {
+                return wanted.is_empty();
+            };
+            return wanted
+                .iter()
+                .all(|(key, value)| metadata.get(key) == Some(value));
+        }
+        true
+    }
+
+    fn filter_segments(
+        segment: &Segment,
+        id: Option<SegmentUuid>,
+        r#type: Option<String>,
+        scope: Option<SegmentScope>,
+        collection: CollectionUuid,
+    ) -> bool {
+        if id.is_some() && id.unwrap() != segment.id {
+            return false;
+        }
+        if let Some(r#type) = r#type {
+            match SegmentType::try_from(r#type.as_str()) {
+                Ok(r#type) if r#type == segment.r#type => {}
+                _ => return false,
+            }
+        }
+        if scope.is_some() && scope.unwrap() != segment.scope {
+            return false;
+        }
+        if collection != segment.collection {
+            return false;
+        }
+        true
+    }
+}
+
+impl TestSysDb {
+    pub async fn get_collections(
+        &mut self,
+        collection_id: Option<CollectionUuid>,
+        name: Option<String>,
+        tenant: Option<String>,
+        database: Option<String>,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Collection>, GetCollectionsError> {
+        self.simulate_latency(SysDbMethod::GetCollections).await;
+        if let Some(InjectedError::GetCollections(error)) =
+            self.take_injected(SysDbMethod::GetCollections)
+        {
+            return Err(error);
+        }
+        let filter = CollectionFilter {
+            collection_id,
+            name,
+            tenant,
+            database,
+            version: None,
+            metadata: None,
+            include_deleted: false,
+        };
+        self.inner
+            .lock()
+            .call_log
+            .push(SysDbCall::GetCollections(filter.clone()));
+        let mut collections = self.find_collections(&filter);
+        collections.sort_by_key(|collection| collection.collection_id);
+        let collections = collections.into_iter().skip(offset as usize);
+        Ok(match limit {
+            Some(limit) => collections.take(limit as usize).collect(),
+            None => collections.collect(),
+        })
+    }
+
+    /// The live collections among `collection_ids`, ordered by id. Unknown ids
+    /// are left out.
+    pub async fn get_collections_by_ids(
+        &self,
+        collection_ids: Vec<CollectionUuid>,
+    ) -> Result<Vec<Collection>, GetCollectionsError> {
+        let inner = self.inner.lock();
+        let mut collections = collection_ids
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter_map(|collection_id| inner.live_collection(&collection_id).cloned())
+            .collect::<Vec<_>>();
+        collections.sort_by_key(|collection| collection.collection_id);
+        Ok(collections)
+    }
+
+    /// Counts the live collections matching the tenant and database, as
+    /// `get_collections` would return them.
+    pub async fn count_collections(
+        &self,
+        tenant: Option<String>,
+        database: Option<String>,
+    ) -> Result<usize, CountCollectionsError> {
+        let filter = CollectionFilter {
+            tenant,
+            database,
+            ..Default::default()
+        };
+        let inner = self.inner.lock();
+        Ok(inner
+            .live_collections()
+            .filter(|collection| TestSysDb::filter_collections(collection, &filter))
+            .count())
+    }
+
+    pub async fn create_collection(
+        &mut self,
+        collection: Collection,
+        segments: Vec<Segment>,
+        get_or_create: bool,
+    ) -> Result<Collection, CreateCollectionError> {
+        if get_or_create {
+            if let Some(existing) = self.inner.lock().live_collection_named(&collection) {
+                return Ok(existing.clone());
+            }
+        }
+        self.create_collection_with_segments(collection, segments)
+    }
+
+    /// Inserts a collection and its segments under one lock. Nothing is
+    /// inserted if the name is taken or any segment id is already in use.
+    pub fn create_collection_with_segments(
+        &mut self,
+        collection: Collection,
+        segments: Vec<Segment>,
+    ) -> Result<Collection, CreateCollectionError> {
+        let mut inner = self.inner.lock();
+        if inner.live_collection_named(&collection).is_some() {
+            return Err(CreateCollectionError::AlreadyExists(collection.name));
+        }
+        // Database names are scoped per tenant, so a name the tenant has not
+        // registered is simply a new database. Only a database referred to by
+        // the id of another tenant's database is a mismatch.
+        if inner
+            .database_named(&collection.tenant, &collection.database)
+            .is_none()
+        {
+            let owned_elsewhere = uuid::Uuid::parse_str(&collection.database)
+                .ok()
+                .and_then(|id| inner.databases.get(&id))
+                .is_some_and(|database| database.tenant != collection.tenant);
+            if owned_elsewhere {
+                return Err(CreateCollectionError::DatabaseTenantMismatch(
+                    collection.database,
+                ));
+            }
+        }
+        let mut segment_ids = HashSet::new();
+        for segment in &segments {
+            if inner.segments.contains_key(&segment.id) || !segment_ids.insert(segment.id) {
+                return Err(CreateCollectionError::Internal(
+                    CreateSegmentError::AlreadyExists(segment.id).boxed(),
+                ));
+            }
+        }
+        inner.insert_collection(collection.clone());
+        for segment in segments {
+            inner.insert_segment(segment);
+        }
+        Ok(collection)
+    }
+
+    pub async fn get_segments(
+        &mut self,
+        id: Option<SegmentUuid>,
+        r#type: Option<String>,
+        scope: Option<SegmentScope>,
+        collection: CollectionUuid,
+    ) -> Result<Vec<Segment>, GetSegmentsError> {
+        self.simulate_latency(SysDbMethod::GetSegments).await;
+        if let Some(InjectedError::GetSegments(error)) =
+            self.take_injected(SysDbMethod::GetSegments)
+        {
+            return Err(error);
+        }
+        let mut inner = self.inner.lock();
+        inner.call_log.push(SysDbCall::GetSegments {
+            id,
+            r#type: r#type.clone(),
+            scope: scope.clone(),
+            collection,
+        });
+        if inner.segment_read_failure {
+            return Err(GetSegmentsError::Unavailable);
+        }
+        let mut segments = Vec::new();
+        for segment in inner.segments.values() {
+            if !TestSysDb::filter_segments(segment, id, r#type.clone(), scope.clone(), collection) {
+                continue;
+            }
+            segments.push(segment.clone());
+        }
+        segments.sort_by_key(|segment| segment.id);
+        Ok(segments)
+    }
+
+    /// The segments of a collection ordered by id. An unknown collection has
+    /// none.
+    pub async fn get_segments_for_collection(
+        &self,
+        collection_id: CollectionUuid,
+    ) -> Result<Vec<Segment>, GetSegmentsError> {
+        let inner = self.inner.lock();
+        if inner.segment_read_failure {
+            return Err(GetSegmentsError::Unavailable);
+        }
+        let mut segments = inner
+            .segments
+            .values()
+            .filter(|segment| segment.collection == collection_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        segments.sort_by_key(|segment| segment.id);
+        Ok(segments)
+    }
+
+    pub async fn count_segments(
+        &self,
+        collection_id: CollectionUuid,
+    ) -> Result<usize, GetSegmentsError> {
+        Ok(self.get_segments_for_collection(collection_id).await?.len())
+    }
+
+    /// Registers a tenant that has never been compacted, along with its
+    /// `default_database`.
+    pub async fn create_tenant(
+        &mut self,
+        tenant_name: String,
+    ) -> Result<CreateTenantResponse, CreateTenantError> {
+        let mut inner = self.inner.lock();
+        if inner.tenant_last_compaction_time.contains_key(&tenant_name) {
+            return Err(CreateTenantError::AlreadyExists(tenant_name));
+        }
+        inner.database(&tenant_name, "default_database");
+        inner.tenant_last_compaction_time.insert(tenant_name, 0);
+        Ok(CreateTenantResponse {})
+    }
+
+    pub async fn get_tenant(
+        &self,
+        tenant_name: String,
+    ) -> Result<GetTenantResponse, GetTenantError> {
+        let inner = self.inner.lock();
+        if !inner.tenant_last_compaction_time.contains_key(&tenant_name) {
+            return Err(GetTenantError::NotFound(tenant_name));
+        }
+        Ok(GetTenantResponse { name: tenant_name })
+    }
+
+    pub async fn create_database(
+        &mut self,
+        database_id: uuid::Uuid,
+        database_name: String,
+        tenant: String,
+    ) -> Result<CreateDatabaseResponse, CreateDatabaseError> {
+        let mut inner = self.inner.lock();
+        if inner.database_named(&tenant, &database_name).is_some()
+            || inner.databases.contains_key(&database_id)
+        {
+            return Err(CreateDatabaseError::AlreadyExists(database_name));
+        }
+        inner.call_log.push(SysDbCall::CreateDatabase {
+            database_id,
+            name: database_name.clone(),
+            tenant: tenant.clone(),
+        });
+        inner.insert_database(Database {
+            id: database_id,
+            name: database_name,
+            tenant,
+        });
+        Ok(CreateDatabaseResponse {})
+    }
+
+    pub async fn get_database(
+        &self,
+        database_name: String,
+        tenant: String,
+    ) -> Result<GetDatabaseResponse, GetDatabaseError> {
+        let inner = self.inner.lock();
+        inner
+            .database_named(&tenant, &database_name)
+            .cloned()
+            .ok_or(GetDatabaseError::NotFound(database_name))
+    }
+
+    /// Deletes a database that no live collection refers to.
+    pub async fn delete_database(
+        &mut self,
+        database_name: String,
+        tenant: String,
+    ) -> Result<DeleteDatabaseResponse, DeleteDatabaseError> {
+        let mut inner = self.inner.lock();
+        let key = (tenant, database_name);
+        let Some(database_id) = inner.database_ids.get(&key).copied() else {
+            return Err(DeleteDatabaseError::NotFound(key.1));
+        };
+        let in_use = inner
+            .live_collections()
+            .any(|collection| collection.tenant == key.0 && collection.database == key.1);
+        if in_use {
+            return Err(DeleteDatabaseError::NotEmpty(key.1));
+        }
+        inner.database_ids.remove(&key);
+        inner.databases.remove(&database_id);
+        Ok(DeleteDatabaseResponse {})
+    }
+
+    pub async fn list_databases(
+        &self,
+        tenant: String,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<ListDatabasesResponse, ListDatabasesError> {
+        let mut inner = self.inner.lock();
+        inner.call_log.push(SysDbCall::ListDatabases {
+            tenant: tenant.clone(),
+            limit,
+            offset,
+        });
+        let mut databases = inner
+            .databases
+            .values()
+            .filter(|database| database.tenant == tenant)
+            .cloned()
+            .collect::<Vec<_>>();
+        databases.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut databases = databases
+            .into_iter()
+            .skip(offset as usize)
+            .collect::<Vec<_>>();
+
+        if let Some(limit_value) = limit {
+            databases.truncate(limit_value as usize);
+        }
+
+        Ok(databases)
+    }
+
+    /// Returns an entry for each known tenant, in request order. Unknown tenants
+    /// are left out rather than failing the whole batch.
+    pub async fn get_last_compaction_time(
+        &mut self,
+        tenant_ids: Vec<String>,
+    ) -> Result<Vec<Tenant>, GetLastCompactionTimeError> {
+        self.simulate_latency(SysDbMethod::GetLastCompactionTime)
+            .await;
+        if let Some(InjectedError::GetLastCompactionTime(error)) =
+            self.take_injected(SysDbMethod::GetLastCompactionTime)
+        {
+            return Err(error);
+        }
+        let inner = self.inner.lock();
+        let tenants = tenant_ids
+            .into_iter()
+            .filter_map(|tenant_id| {
+                inner
+                    .tenant_last_compaction_time
+                    .get(&tenant_id)
+                    .map(|last_compaction_time| Tenant {
+                        id: tenant_id.clone(),
+                        last_compaction_time: *last_compaction_time,
+                    })
+            })
+            .collect();
+        Ok(tenants)
+    }
+
+    pub async fn set_last_compaction_time(
+        &mut self,
+        tenant_id: String,
+        last_compaction_time: i64,
+    ) -> Result<(), SetLastCompactionTimeError> {
+        let mut inner = self.inner.lock();
+        match inner.tenant_last_compaction_time.get_mut(&tenant_id) {
+            Some(current) => {
+                *current = last_compaction_time;
+                Ok(())
+            }
+            None => Err(SetLastCompactionTimeError::TenantNotFound),
+        }
+    }
+
+    pub async fn flush_compaction(
+        &mut self,
+        tenant_id: String,
+        collection_id: CollectionUuid,
+        log_position: i64,
+        collection_version: i32,
+        segment_flush_info: Arc<[SegmentFlushInfo]>,
+        total_records_post_compaction: u64,
+    ) -> Result<FlushCompactionResponse, FlushCompactionError> {
+        self.flush(FlushCompactionRequest::new(
+            tenant_id,
+            collection_id,
+            log_position,
+            collection_version,
+            segment_flush_info,
+            total_records_post_compaction,
+        ))
+        .await
+    }
+
+    pub async fn flush(
+        &mut self,
+        request: FlushCompactionRequest,
+    ) -> Result<FlushCompactionResponse, FlushCompactionError> {
+        self.simulate_latency(SysDbMethod::FlushCompaction).await;
+        if let Some(InjectedError::FlushCompaction(error)) =
+            self.take_injected(SysDbMethod::FlushCompaction)
+        {
+            return Err(error);
+        }
+        // A retried flush gets its recorded response back without being
+        // validated again, since the sysdb may have moved on since it was applied.
+        let validator = {
+            let inner = self.inner.lock();
+            if let Some(key) = &request.idempotency_key {
+                if let Some(response) = inner.flush_responses.get(key) {
+                    return Ok(response.clone());
+                }
+            }
+            inner.flush_validator.clone()
+        };
+        // Run the validator without holding the lock so it may inspect the sysdb.
+        if let Some(FlushValidator(validator)) = validator {
+            validator(&request).map_err(FlushCompactionError::ValidationFailed)?;
+        }
+        let recorded = request.clone();
+        let FlushCompactionRequest {
+            tenant_id,
+            collection_id,
+            log_position,
+            collection_version,
+            segment_flush_info,
+            total_records_post_compaction,
+            segment_sizes,
+            idempotency_key,
+            dimension,
+            ..
+        } = request;
+        let (collection, last_compaction_time, flush_pause) = {
+            let mut inner = self.inner.lock();
+            if let Some(key) = &idempotency_key {
+                if let Some(response) = inner.flush_responses.get(key) {
+                    return Ok(response.clone());
+                }
+            }
+            if let Some(available_space) = inner.available_space {
+                if segment_sizes.values().sum::<u64>() > available_space {
+                    return Err(FlushCompactionError::InsufficientSpace);
+                }
+            }
+            let has_empty_path = segment_flush_info.iter().any(|info| {
+                info.file_paths
+                    .values()
+                    .any(|paths| paths.iter().any(|path| path.is_empty()))
+            });
+            if has_empty_path {
+                return Err(FlushCompactionError::InvalidPath);
+            }
+            let collection = inner
+                .live_collection(&collection_id)
+                .ok_or(FlushCompactionError::CollectionNotFound)?;
+            // Every flushed segment must belong to the collection being compacted.
+            let foreign_segment = segment_flush_info.iter().any(|info| {
+                !matches!(
+                    inner.segments.get(&info.segment_id),
+                    Some(segment) if segment.collection == collection_id
+                )
+            });
+            if foreign_segment {
+                return Err(FlushCompactionError::SegmentNotFound);
+            }
+            // Reject flushes computed against a version that has since moved on.
+            let lenient_version_check = inner
+                .tenant_flags
+                .get(&tenant_id)
+                .is_some_and(|flags| flags.contains(LENIENT_VERSION_CHECK_FLAG));
+            if !lenient_version_check && collection.version != collection_version {
+                return Err(FlushCompactionError::VersionMismatch);
+            }
+            let mut collection = collection.clone();
+            if let Some(dimension) = dimension {
+                let requested = dimension as i32;
+                match collection.dimension {
+                    Some(current) if current != requested => {
+                        return Err(FlushCompactionError::DimensionMismatch { current, requested });
+                    }
+                    _ => collection.dimension = Some(requested),
+                }
+            }
+            collection.log_position = log_position;
+            collection.version = collection_version + 1;
+            collection.total_records_post_compaction = total_records_post_compaction;
+            inner
+                .collections
+                .insert(collection.collection_id, collection.clone());
+            let mut last_compaction_time = match inner.tenant_last_compaction_time.get(&tenant_id) {
+                Some(last_compaction_time) => *last_compaction_time,
+                None => 0,
+            };
+            last_compaction_time += 1;
+            match inner.flush_pause {
+                Some(flush_pause) => (collection, last_compaction_time, flush_pause),
+                None => return Ok(inner.finish_flush(recorded, collection, last_compaction_time)),
+            }
+        };
+
+        // Let concurrent readers observe the new version with the old segments.
+        tokio::time::sleep(flush_pause).await;
+        let mut inner = self.inner.lock();
+        Ok(inner.finish_flush(recorded, collection, last_compaction_time))
+    }
+
+    /// Stages a flush without applying it.
+    pub fn prepare_flush(
+        &mut self,
+        request: FlushCompactionRequest,
+    ) -> Result<FlushToken, FlushCompactionError> {
+        let mut inner = self.inner.lock();
+        if inner.live_collection(&request.collection_id).is_none() {
+            return Err(FlushCompactionError::CollectionNotFound);
+        }
+        let token = FlushToken(inner.new_uuid());
+        inner.pending_flushes.insert(token, request);
+        Ok(token)
+    }
+
+    pub async fn commit_flush(
+        &mut self,
+        token: FlushToken,
+    ) -> Result<FlushCompactionResponse, FlushCompactionError> {
+        let request = {
+            let mut inner = self.inner.lock();
+            inner
+                .pending_flushes
+                .remove(&token)
+                .ok_or(FlushCompactionError::FlushTokenNotFound)?
+        };
+        self.flush(request).await
+    }
+
+    pub fn abort_flush(&mut self, token: FlushToken) -> Result<(), FlushCompactionError> {
+        let mut inner = self.inner.lock();
+        inner
+            .pending_flushes
+            .remove(&token)
+            .map(|_| ())
+            .ok_or(FlushCompactionError::FlushTokenNotFound)
+    }
+
+    /// Marks versions so a later `delete_collection_version` may remove them.
+    /// Nothing is marked unless every version exists and none is live.
+    pub async fn mark_version_for_deletion(
+        &self,
+        epoch_id: i64,
+        versions: Vec<VersionListForCollection>,
+    ) -> Result<(), MarkVersionForDeletionError> {
+        let mut inner = self.inner.lock();
+        if epoch_id < inner.epoch {
+            return Err(MarkVersionForDeletionError::EpochMismatch);
+        }
+        let mut to_mark = Vec::new();
+        for version_list in &versions {
+            let collection_id = CollectionUuid::from_str(&version_list.collection_id).ok();
+            let live_version = collection_id
+                .and_then(|collection_id| inner.collections.get(&collection_id))
+                .map(|collection| collection.version as i64);
+            for version in &version_list.versions {
+                let recorded = collection_id
+                    .and_then(|collection_id| inner.versions.get(&collection_id))
+                    .is_some_and(|records| {
+                        records
+                            .iter()
+                            .any(|record| record.version as i64 == *version)
+                    });
+                if !recorded {
+                    return Err(MarkVersionForDeletionError::VersionNotFound {
+                        collection_id: version_list.collection_id.clone(),
+                        version: *version,
+                    });
+                }
+                if live_version == Some(*version) {
+                    return Err(MarkVersionForDeletionError::LiveVersion(*version));
+                }
+            }
+            if let Some(collection_id) = collection_id {
+                to_mark.push((collection_id, &version_list.versions));
+            }
+        }
+        for (collection_id, marked) in to_mark {
+            if let Some(records) = inner.versions.get_mut(&collection_id) {
+                for record in records.iter_mut() {
+                    if marked.contains(&(record.version as i64)) {
+                        record.marked_for_deletion = true;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes marked versions along with the file paths only they recorded.
+    /// A collection whose list names an unmarked or live version reports
+    /// `false` and keeps all of its versions.
+    pub async fn delete_collection_version(
+        &self,
+        versions: Vec<VersionListForCollection>,
+    ) -> HashMap<String, bool> {
+        let mut inner = self.inner.lock();
+        let mut results = HashMap::new();
+        for version_list in versions {
+            let collection_id = CollectionUuid::from_str(&version_list.collection_id).ok();
+            let live_version = collection_id
+                .and_then(|collection_id| inner.collections.get(&collection_id))
+                .map(|collection| collection.version as i64);
+            let records =
+                collection_id.and_then(|collection_id| inner.versions.get_mut(&collection_id));
+            let deleted = match records {
+                Some(records) => {
+                    let all_marked = version_list.versions.iter().all(|version| {
+                        live_version != Some(*version)
+                            && records.iter().any(|record| {
+                                record.version as i64 == *version && record.marked_for_deletion
+                            })
+                    });
+                    if all_marked {
+                        records.retain(|record| {
+                            !version_list.versions.contains(&(record.version as i64))
+                        });
+                    }
+                    all_marked
+                }
+                None => version_list.versions.is_empty(),
+            };
+            results.insert(version_list.collection_id, deleted);
+        }
+        results
+    }
+
+    /// File paths a collection still records, through its live segments or any
+    /// remaining version.
+    pub fn recorded_file_paths(&self, collection_id: CollectionUuid) -> Vec<String> {
+        let inner = self.inner.lock();
+        let live = inner
+            .segments
+            .values()
+            .filter(|segment| segment.collection == collection_id);
+        let versioned = inner
+            .versions
+            .get(&collection_id)
+            .into_iter()
+            .flatten()
+            .flat_map(|record| record.segments.iter());
+        let paths = live
+            .chain(versioned)
+            .flat_map(|segment| segment.file_path.values().flatten().cloned())
+            .collect::<HashSet<_>>();
+        let mut paths = paths.into_iter().collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    /// Every version still recorded for a collection, oldest first.
+    pub fn get_collection_versions(&self, collection_id: CollectionUuid) -> Vec<VersionRecord> {
+        let inner = self.inner.lock();
+        inner
+            .versions
+            .get(&collection_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Collections holding at least one marked version created before
+    /// `cutoff_time`, ordered by id.
+    pub async fn list_collections_to_gc(
+        &self,
+        cutoff_time: i64,
+        limit: Option<u32>,
+    ) -> Result<Vec<CollectionToGcInfo>, GetCollectionsToGcError> {
+        let inner = self.inner.lock();
+        let mut collections = inner
+            .versions
+            .iter()
+            .filter(|(_, versions)| {
+                versions
+                    .iter()
+                    .any(|record| record.marked_for_deletion && record.created_at < cutoff_time)
+            })
+            .filter_map(|(collection_id, _)| inner.collections.get(collection_id))
+            .map(|collection| CollectionToGcInfo {
+                id: collection.collection_id,
+                name: collection.name.clone(),
+                version_file_path: inner
+                    .version_files
+                    .get(&collection.collection_id)
+                    .map(|(path, _)| path.clone())
+                    .unwrap_or_default(),
+                latest_version: collection.version as i64,
+            })
+            .collect::<Vec<_>>();
+        collections.sort_by_key(|collection| collection.id);
+        if let Some(limit) = limit {
+            collections.truncate(limit as usize);
+        }
+        Ok(collections)
+    }
+
+    pub async fn get_collection_size(
+        &self,
+        collection_id: CollectionUuid,
+    ) -> Result<usize, GetCollectionSizeError> {
+        let inner = self.inner.lock();
+        let collection = inner.live_collection(&collection_id);
+        match collection {
+            Some(collection) => Ok(collection.total_records_post_compaction as usize),
+            None => Err(GetCollectionSizeError::NotFound(
+                "Collection not found".to_string(),
+            )),
+        }
+    }
+
+    /// Sums the record counts of the collection's segments instead of trusting
+    /// its cached `total_records_post_compaction`.
+    pub async fn compute_collection_size_from_segments(
+        &self,
+        collection_id: CollectionUuid,
+    ) -> Result<usize, GetCollectionSizeError> {
+        let inner = self.inner.lock();
+        if inner.live_collection(&collection_id).is_none() {
+            return Err(GetCollectionSizeError::NotFound(
+                "Collection not found".to_string(),
+            ));
+        }
+        let size = inner
+            .segments
+            .values()
+            .filter(|segment| segment.collection == collection_id)
+            .filter_map(|segment| inner.segment_record_counts.get(&segment.id))
+            .sum::<u64>();
+        Ok(size as usize)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DatabaseDimensionError {
+    #[error("Collections in the database have different dimensions")]
+    Mismatch,
+}
+
+impl ChromaError for DatabaseDimensionError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            DatabaseDimensionError::Mismatch => ErrorCodes::FailedPrecondition,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ResolveError {
+    #[error("Database [{0}] does not exist")]
+    DatabaseNotFound(String),
+    #[error("Collection [{0}] does not exist")]
+    CollectionNotFound(String),
+}
+
+impl ChromaError for ResolveError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            ResolveError::DatabaseNotFound(_) => ErrorCodes::NotFound,
+            ResolveError::CollectionNotFound(_) => ErrorCodes::NotFound,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SegmentDeltaError {
+    #[error("Collection [{0}] does not exist")]
+    CollectionNotFound(CollectionUuid),
+    #[error("Segment [{0}] does not exist in the collection")]
+    SegmentNotFound(SegmentUuid),
+    #[error("Segment [{0}] belongs to another collection")]
+    WrongCollection(SegmentUuid),
+}
+
+impl ChromaError for SegmentDeltaError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            SegmentDeltaError::CollectionNotFound(_) => ErrorCodes::NotFound,
+            SegmentDeltaError::SegmentNotFound(_) => ErrorCodes::NotFound,
+            SegmentDeltaError::WrongCollection(_) => ErrorCodes::InvalidArgument,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CasError {
+    #[error("Collection [{0}] does not exist")]
+    NotFound(CollectionUuid),
+    #[error("Expected collection version {expected}, found {actual}")]
+    Mismatch { expected: i32, actual: i32 },
+}
+
+impl ChromaError for CasError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            CasError::NotFound(_) => ErrorCodes::NotFound,
+            CasError::Mismatch { .. } => ErrorCodes::VersionMismatch,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RestoreError {
+    #[error("Collection [{0}] already exists")]
+    AlreadyExists(CollectionUuid),
+}
+
+impl ChromaError for RestoreError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            RestoreError::AlreadyExists(_) => ErrorCodes::AlreadyExists,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ForkCollectionError {
+    #[error("Collection [{0}] does not exist")]
+    NotFound(CollectionUuid),
+    #[error("Collection [{0}] already exists")]
+    AlreadyExists(CollectionUuid),
+}
+
+impl ChromaError for ForkCollectionError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            ForkCollectionError::NotFound(_) => ErrorCodes::NotFound,
+            ForkCollectionError::AlreadyExists(_) => ErrorCodes::AlreadyExists,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CreateSegmentError {
+    #[error("Segment [{0}] already exists")]
+    AlreadyExists(SegmentUuid),
+}
+
+impl ChromaError for CreateSegmentError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            CreateSegmentError::AlreadyExists(_) => ErrorCodes::AlreadyExists,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DeleteSegmentError {
+    #[error("Segment [{0}] not found")]
+    NotFound(SegmentUuid),
+}
+
+impl ChromaError for DeleteSegmentError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            DeleteSegmentError::NotFound(_) => ErrorCodes::NotFound,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("Replaying call {index} failed: {source}")]
+    CreateDatabase {
+        index: usize,
+        source: CreateDatabaseError,
+    },
+    #[error("Replaying call {index} failed: {source}")]
+    Flush {
+        index: usize,
+        source: FlushCompactionError,
+    },
+}
+
+impl ChromaError for ReplayError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            ReplayError::CreateDatabase { source, .. } => source.code(),
+            ReplayError::Flush { source, .. } => source.code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SysDb;
+    use chroma_types::{test_segment, GetCollectionWithSegmentsError, UpdateMetadataValue};
+
+    // Adds a collection and flushes it until it reaches `version`.
+    async fn flushed_collection(sysdb: &mut TestSysDb, version: i32) -> CollectionUuid {
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        for current in 0..version {
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    current as i64 + 1,
+                    current,
+                    Arc::new([]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+        collection_id
+    }
+
+    fn test_collection(tenant: &str, database: &str, name: &str) -> Collection {
+        Collection {
+            name: name.to_string(),
+            tenant: tenant.to_string(),
+            database: database.to_string(),
+            ..Collection::test_collection(3)
+        }
+    }
+
+    #[test]
+    fn test_accessible_databases() {
+        let mut sysdb = TestSysDb::new();
+        sysdb.add_collection(test_collection("tenant", "database_a", "collection_a"));
+        sysdb.add_collection(test_collection("tenant", "database_b", "collection_b"));
+
+        sysdb.grant_access(
+            "principal".to_string(),
+            "tenant".to_string(),
+            "database_a".to_string(),
+        );
+        let databases = sysdb.accessible_databases("principal".to_string());
+        assert_eq!(databases.len(), 1);
+        assert_eq!(databases[0].name, "database_a");
+        assert_eq!(databases[0].tenant, "tenant");
+
+        assert!(sysdb
+            .accessible_databases("other_principal".to_string())
+            .is_empty());
+
+        sysdb.revoke_access(
+            "principal".to_string(),
+            "tenant".to_string(),
+            "database_a".to_string(),
+        );
+        assert!(sysdb
+            .accessible_databases("principal".to_string())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_collections_with_missing_scopes() {
+        let mut sysdb = TestSysDb::new();
+        let complete = test_collection("tenant", "database", "complete");
+        let incomplete = test_collection("tenant", "database", "incomplete");
+        for scope in [
+            SegmentScope::METADATA,
+            SegmentScope::RECORD,
+            SegmentScope::VECTOR,
+        ] {
+            sysdb.add_segment(test_segment(complete.collection_id, scope));
+        }
+        sysdb.add_segment(test_segment(
+            incomplete.collection_id,
+            SegmentScope::METADATA,
+        ));
+        sysdb.add_segment(test_segment(incomplete.collection_id, SegmentScope::RECORD));
+        let incomplete_id = incomplete.collection_id;
+        sysdb.add_collection(complete);
+        sysdb.add_collection(incomplete);
+
+        let missing = sysdb.collections_with_missing_scopes(vec![
+            SegmentScope::METADATA,
+            SegmentScope::RECORD,
+            SegmentScope::VECTOR,
+        ]);
+        assert_eq!(missing, vec![(incomplete_id, vec![SegmentScope::VECTOR])]);
+    }
+
+    #[tokio::test]
+    async fn test_total_flush_bytes() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(segment);
+
+        for (version, size) in [(0, 100), (1, 250)] {
+            let mut request = FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                version as i64,
+                version,
+                Arc::new([]),
+                0,
+            );
+            request.segment_sizes.insert(segment_id, size);
+            sysdb.flush(request).await.unwrap();
+        }
+
+        assert_eq!(sysdb.total_flush_bytes(collection_id), 350);
+        assert_eq!(sysdb.total_flush_bytes(CollectionUuid::new()), 0);
+    }
+
+    #[test]
+    fn test_tenant_snapshot() {
+        let mut sysdb = TestSysDb::new();
+        let mine = test_collection("tenant_1", "database", "mine");
+        let theirs = test_collection("tenant_2", "database", "theirs");
+        let mine_id = mine.collection_id;
+        sysdb.add_segment(test_segment(mine_id, SegmentScope::RECORD));
+        sysdb.add_segment(test_segment(theirs.collection_id, SegmentScope::RECORD));
+        sysdb.add_collection(mine);
+        sysdb.add_collection(theirs);
+        sysdb.add_tenant_last_compaction_time("tenant_1".to_string(), 10);
+        sysdb.add_tenant_last_compaction_time("tenant_2".to_string(), 20);
+
+        let snapshot = sysdb.tenant_snapshot("tenant_1".to_string());
+        assert_eq!(snapshot.collections.len(), 1);
+        assert_eq!(snapshot.collections[0].collection_id, mine_id);
+        assert_eq!(snapshot.segments.len(), 1);
+        assert_eq!(snapshot.segments[0].collection, mine_id);
+        assert_eq!(snapshot.databases.len(), 1);
+        assert_eq!(snapshot.databases[0].tenant, "tenant_1");
+        assert_eq!(snapshot.last_compaction_time, Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_tenant_snapshot_omits_absent_database() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        // Leave a live collection behind in a database that is gone.
+        sysdb.delete_collection(collection_id).await.unwrap();
+        sysdb
+            .delete_database("database".to_string(), "tenant".to_string())
+            .await
+            .unwrap();
+        sysdb.undelete_collection(collection_id).unwrap();
+
+        let snapshot = sysdb.tenant_snapshot("tenant".to_string());
+        assert_eq!(snapshot.collections.len(), 1);
+        assert!(snapshot.databases.is_empty());
+        let databases = sysdb
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap();
+        assert!(databases.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flush_idempotency_key() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+
+        let mut request = FlushCompactionRequest::new(
+            "tenant".to_string(),
+            collection_id,
+            10,
+            0,
+            Arc::new([]),
+            0,
+        );
+        request.idempotency_key = Some("flush-1".to_string());
+        let first = sysdb.flush(request.clone()).await.unwrap();
+        // The retry must not be applied, whatever else it carries.
+        request.collection_version = 1;
+        request.log_position = 20;
+        let retried = sysdb.flush(request).await.unwrap();
+
+        assert_eq!(first.collection_version, 1);
+        assert_eq!(retried.collection_version, 1);
+        let collections = sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(collections[0].version, 1);
+        assert_eq!(collections[0].log_position, 10);
+    }
+
+    #[test]
+    fn test_list_collections_by_creation_order() {
+        let mut sysdb = TestSysDb::new();
+        let mut created = Vec::new();
+        for name in ["c", "a", "b"] {
+            let collection = test_collection("tenant", "database", name);
+            created.push(collection.collection_id);
+            sysdb.add_collection(collection);
+        }
+        sysdb.add_collection(test_collection("other_tenant", "database", "d"));
+
+        let listed = sysdb
+            .list_collections_by_creation_order(Some("tenant".to_string()))
+            .into_iter()
+            .map(|collection| collection.collection_id)
+            .collect::<Vec<_>>();
+        assert_eq!(listed, created);
+        assert_eq!(sysdb.list_collections_by_creation_order(None).len(), 4);
+    }
+
+    #[test]
+    fn test_segment_file_count() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let mut record = test_segment(collection_id, SegmentScope::RECORD);
+        record.file_path = HashMap::from([
+            ("a".to_string(), vec!["a/1".to_string(), "a/2".to_string()]),
+            ("b".to_string(), vec!["b/1".to_string()]),
+        ]);
+        let mut vector = test_segment(collection_id, SegmentScope::VECTOR);
+        vector.file_path = HashMap::from([("c".to_string(), vec!["c/1".to_string()])]);
+        let mut unrelated = test_segment(CollectionUuid::new(), SegmentScope::RECORD);
+        unrelated.file_path = HashMap::from([("d".to_string(), vec!["d/1".to_string()])]);
+        sysdb.add_collection(collection);
+        sysdb.add_segment(record);
+        sysdb.add_segment(vector);
+        sysdb.add_segment(unrelated);
+
+        assert_eq!(sysdb.segment_file_count(collection_id).unwrap(), 4);
+        assert!(matches!(
+            sysdb.segment_file_count(CollectionUuid::new()),
+            Err(GetCollectionsError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_flush_insufficient_space() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(segment);
+        sysdb.set_available_space(100);
+
+        let mut request = FlushCompactionRequest::new(
+            "tenant".to_string(),
+            collection_id,
+            10,
+            0,
+            Arc::new([]),
+            0,
+        );
+        request.segment_sizes.insert(segment_id, 101);
+        let result = sysdb.flush(request.clone()).await;
+        assert!(matches!(
+            result,
+            Err(FlushCompactionError::InsufficientSpace)
+        ));
+        assert_eq!(sysdb.total_flush_bytes(collection_id), 0);
+
+        request.segment_sizes.insert(segment_id, 100);
+        assert!(sysdb.flush(request).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_collection_at_version() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+
+        for (version, log_position) in [(0, 10), (1, 20)] {
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    log_position,
+                    version,
+                    Arc::new([]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+
+        let earlier = sysdb.get_collection_at_version(collection_id, 1).unwrap();
+        assert_eq!(earlier.version, 1);
+        assert_eq!(earlier.log_position, 10);
+        let latest = sysdb.get_collection_at_version(collection_id, 2).unwrap();
+        assert_eq!(latest.log_position, 20);
+        assert!(matches!(
+            sysdb.get_collection_at_version(collection_id, 3),
+            Err(GetCollectionsError::VersionNotFound(3))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_segments_updated_since() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let record = test_segment(collection_id, SegmentScope::RECORD);
+        let record_id = record.id;
+        sysdb.add_collection(collection);
+        sysdb.set_now(100);
+        sysdb.add_segment(record);
+        sysdb.add_segment(test_segment(collection_id, SegmentScope::VECTOR));
+
+        sysdb.set_now(200);
+        sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                10,
+                0,
+                Arc::new([SegmentFlushInfo {
+                    segment_id: record_id,
+                    file_paths: HashMap::new(),
+                }]),
+                0,
+            ))
+            .await
+            .unwrap();
+
+        let updated = sysdb.segments_updated_since(150);
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].id, record_id);
+        assert_eq!(sysdb.segments_updated_since(50).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_max_version_history() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        sysdb.set_max_version_history(3);
+
+        for version in 0..5 {
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    version as i64,
+                    version,
+                    Arc::new([]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+
+        for version in [1, 2] {
+            assert!(matches!(
+                sysdb.get_collection_at_version(collection_id, version),
+                Err(GetCollectionsError::VersionNotFound(_))
+            ));
+        }
+        for version in [3, 4, 5] {
+            assert!(sysdb
+                .get_collection_at_version(collection_id, version)
+                .is_ok());
+        }
+        assert_eq!(sysdb.auto_pruned_versions(collection_id), vec![1, 2]);
+
+        sysdb.set_max_version_history(0);
+        sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                5,
+                5,
+                Arc::new([]),
+                0,
+            ))
+            .await
+            .unwrap();
+        assert!(sysdb.get_collection_at_version(collection_id, 6).is_ok());
+        assert_eq!(
+            sysdb.auto_pruned_versions(collection_id),
+            vec![1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn test_database_dimension() {
+        let mut sysdb = TestSysDb::new();
+        for (database, name, dimension) in [
+            ("matching", "a", Some(3)),
+            ("matching", "b", Some(3)),
+            ("matching", "c", None),
+            ("mismatching", "d", Some(3)),
+            ("mismatching", "e", Some(4)),
+        ] {
+            let mut collection = test_collection("tenant", database, name);
+            collection.dimension = dimension;
+            sysdb.add_collection(collection);
+        }
+
+        assert_eq!(
+            sysdb
+                .database_dimension("tenant".to_string(), "matching".to_string())
+                .unwrap(),
+            Some(3)
+        );
+        assert!(matches!(
+            sysdb.database_dimension("tenant".to_string(), "mismatching".to_string()),
+            Err(DatabaseDimensionError::Mismatch)
+        ));
+        assert_eq!(
+            sysdb
+                .database_dimension("tenant".to_string(), "empty".to_string())
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collections_with_pending_flushes() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        let request = FlushCompactionRequest::new(
+            "tenant".to_string(),
+            collection_id,
+            10,
+            0,
+            Arc::new([]),
+            0,
+        );
+
+        let token = sysdb.prepare_flush(request.clone()).unwrap();
+        assert_eq!(
+            sysdb.collections_with_pending_flushes(),
+            vec![collection_id]
+        );
+        let response = sysdb.commit_flush(token).await.unwrap();
+        assert_eq!(response.collection_version, 1);
+        assert!(sysdb.collections_with_pending_flushes().is_empty());
+
+        let token = sysdb.prepare_flush(request).unwrap();
+        assert_eq!(
+            sysdb.collections_with_pending_flushes(),
+            vec![collection_id]
+        );
+        sysdb.abort_flush(token).unwrap();
+        assert!(sysdb.collections_with_pending_flushes().is_empty());
+        assert!(matches!(
+            sysdb.commit_flush(token).await,
+            Err(FlushCompactionError::FlushTokenNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mark_version_for_deletion_stale_epoch() {
+        let mut sysdb = TestSysDb::new();
+        let collection_id = flushed_collection(&mut sysdb, 3).await;
+        let versions = vec![VersionListForCollection {
+            collection_id: collection_id.to_string(),
+            database_id: "database".to_string(),
+            tenant_id: "tenant".to_string(),
+            versions: vec![2],
+        }];
+        assert_eq!(sysdb.next_epoch(), 1);
+        assert_eq!(sysdb.next_epoch(), 2);
+
+        assert!(matches!(
+            sysdb.mark_version_for_deletion(1, versions.clone()).await,
+            Err(MarkVersionForDeletionError::EpochMismatch)
+        ));
+        assert!(sysdb.mark_version_for_deletion(2, versions).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_list_collections_to_gc() {
+        let mut sysdb = TestSysDb::new();
+        let mark = |collection_id: CollectionUuid| {
+            vec![VersionListForCollection {
+                collection_id: collection_id.to_string(),
+                database_id: "database".to_string(),
+                tenant_id: "tenant".to_string(),
+                versions: vec![1],
+            }]
+        };
+        sysdb.set_now(10);
+        let stale_id = flushed_collection(&mut sysdb, 2).await;
+        let unmarked_id = flushed_collection(&mut sysdb, 2).await;
+        sysdb.set_now(100);
+        let recent_id = flushed_collection(&mut sysdb, 2).await;
+        sysdb
+            .mark_version_for_deletion(0, mark(stale_id))
+            .await
+            .unwrap();
+        sysdb
+            .mark_version_for_deletion(0, mark(recent_id))
+            .await
+            .unwrap();
+
+        let to_gc = sysdb.list_collections_to_gc(50, None).await.unwrap();
+        assert_eq!(to_gc.len(), 1);
+        assert_eq!(to_gc[0].id, stale_id);
+        assert_eq!(to_gc[0].latest_version, 2);
+
+        let mut expected = vec![stale_id, recent_id];
+        expected.sort();
+        let to_gc = sysdb.list_collections_to_gc(200, None).await.unwrap();
+        assert_eq!(
+            to_gc
+                .iter()
+                .map(|collection| collection.id)
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert!(!to_gc.iter().any(|collection| collection.id == unmarked_id));
+        assert_eq!(
+            sysdb
+                .list_collections_to_gc(200, Some(1))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_collection_version_purges_paths() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(segment);
+        for (version, path) in ["blocks/1", "blocks/2", "blocks/3"].iter().enumerate() {
+            let flush_info = SegmentFlushInfo {
+                segment_id,
+                file_paths: HashMap::from([("blocks".to_string(), vec![path.to_string()])]),
+            };
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    version as i64 + 1,
+                    version as i32,
+                    Arc::new([flush_info]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+        let version_list = |versions: Vec<i64>| {
+            vec![VersionListForCollection {
+                collection_id: collection_id.to_string(),
+                database_id: "database".to_string(),
+                tenant_id: "tenant".to_string(),
+                versions,
+            }]
+        };
+        sysdb
+            .mark_version_for_deletion(0, version_list(vec![1]))
+            .await
+            .unwrap();
+
+        // The live version can never be deleted, even alongside a marked one.
+        let results = sysdb
+            .delete_collection_version(version_list(vec![1, 3]))
+            .await;
+        assert_eq!(results.get(&collection_id.to_string()), Some(&false));
+        assert_eq!(
+            sysdb.recorded_file_paths(collection_id),
+            vec!["blocks/1", "blocks/2", "blocks/3"]
+        );
+
+        let paths_at = |sysdb: &TestSysDb, version| {
+            sysdb
+                .get_segment_file_paths_at_version(segment_id, version)
+                .map(|paths| paths["blocks"].clone())
+        };
+        assert_eq!(paths_at(&sysdb, 1), Some(vec!["blocks/1".to_string()]));
+        assert_eq!(paths_at(&sysdb, 2), Some(vec!["blocks/2".to_string()]));
+
+        let results = sysdb.delete_collection_version(version_list(vec![1])).await;
+        assert_eq!(results.get(&collection_id.to_string()), Some(&true));
+        assert_eq!(
+            sysdb.recorded_file_paths(collection_id),
+            vec!["blocks/2", "blocks/3"]
+        );
+        assert_eq!(paths_at(&sysdb, 1), None);
+        assert_eq!(paths_at(&sysdb, 3), Some(vec!["blocks/3".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_mark_and_delete_collection_versions() {
+        let mut sysdb = TestSysDb::new();
+        let collection_id = flushed_collection(&mut sysdb, 3).await;
+        let version_list = |versions: Vec<i64>| {
+            vec![VersionListForCollection {
+                collection_id: collection_id.to_string(),
+                database_id: "database".to_string(),
+                tenant_id: "tenant".to_string(),
+                versions,
+            }]
+        };
+
+        // Version 1 isn't marked yet, so nothing is deleted.
+        let results = sysdb.delete_collection_version(version_list(vec![1])).await;
+        assert_eq!(results.get(&collection_id.to_string()), Some(&false));
+
+        assert!(matches!(
+            sysdb
+                .mark_version_for_deletion(0, version_list(vec![1, 9]))
+                .await,
+            Err(MarkVersionForDeletionError::VersionNotFound { version: 9, .. })
+        ));
+        assert!(matches!(
+            sysdb
+                .mark_version_for_deletion(0, version_list(vec![1, 3]))
+                .await,
+            Err(MarkVersionForDeletionError::LiveVersion(3))
+        ));
+        assert!(sysdb
+            .get_collection_versions(collection_id)
+            .iter()
+            .all(|record| !record.marked_for_deletion));
+
+        sysdb
+            .mark_version_for_deletion(0, version_list(vec![1, 2]))
+            .await
+            .unwrap();
+        let marked = sysdb
+            .get_collection_versions(collection_id)
+            .into_iter()
+            .map(|record| (record.version, record.marked_for_deletion))
+            .collect::<Vec<_>>();
+        assert_eq!(marked, vec![(1, true), (2, true), (3, false)]);
+
+        let results = sysdb.delete_collection_version(version_list(vec![1])).await;
+        assert_eq!(results.get(&collection_id.to_string()), Some(&true));
+        let remaining = sysdb
+            .get_collection_versions(collection_id)
+            .into_iter()
+            .map(|record| record.version)
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_collections_with_size_change() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        assert!(sysdb.collections_with_size_change().is_empty());
+
+        sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                10,
+                0,
+                Arc::new([]),
+                42,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            sysdb.collections_with_size_change(),
+            vec![(collection_id, 42)]
+        );
+        assert!(sysdb.collections_with_size_change().is_empty());
+
+        sysdb.update_collection_size(collection_id, 40);
+        assert_eq!(
+            sysdb.collections_with_size_change(),
+            vec![(collection_id, -2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_segment_read_failure() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_segment(test_segment(collection_id, SegmentScope::RECORD));
+        sysdb.add_collection(collection);
+        sysdb.set_segment_read_failure(true);
+
+        assert!(matches!(
+            sysdb.get_segments(None, None, None, collection_id).await,
+            Err(GetSegmentsError::Unavailable)
+        ));
+        let collections = sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(collections.len(), 1);
+
+        sysdb.set_segment_read_failure(false);
+        assert_eq!(
+            sysdb
+                .get_segments(None, None, None, collection_id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_idle_tenants() {
+        let mut sysdb = TestSysDb::new();
+        sysdb.add_tenant_last_compaction_time("stale".to_string(), 10);
+        sysdb.add_tenant_last_compaction_time("boundary".to_string(), 100);
+        sysdb.add_tenant_last_compaction_time("fresh".to_string(), 200);
+        sysdb.add_collection(test_collection("never_compacted", "database", "collection"));
+
+        assert_eq!(
+            sysdb.idle_tenants(100),
+            vec!["never_compacted".to_string(), "stale".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_collection_full() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+
+        let (collection, database) = sysdb
+            .resolve_collection_full(
+                "tenant".to_string(),
+                "database".to_string(),
+                "collection".to_string(),
+            )
+            .unwrap();
+        assert_eq!(collection.collection_id, collection_id);
+        assert_eq!(database.name, "database");
+        assert_eq!(database.tenant, "tenant");
+
+        assert!(matches!(
+            sysdb.resolve_collection_full(
+                "tenant".to_string(),
+                "missing".to_string(),
+                "collection".to_string(),
+            ),
+            Err(ResolveError::DatabaseNotFound(_))
+        ));
+        assert!(matches!(
+            sysdb.resolve_collection_full(
+                "tenant".to_string(),
+                "database".to_string(),
+                "missing".to_string(),
+            ),
+            Err(ResolveError::CollectionNotFound(_))
+        ));
+
+        sysdb.delete_collection(collection_id).await.unwrap();
+        assert!(matches!(
+            sysdb.resolve_collection_full(
+                "tenant".to_string(),
+                "database".to_string(),
+                "collection".to_string(),
+            ),
+            Err(ResolveError::CollectionNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_compaction_rate() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+
+        for (version, now) in [(0, 5), (1, 12), (2, 15), (3, 18)] {
+            sysdb.set_now(now);
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    version as i64,
+                    version,
+                    Arc::new([]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+
+        // Only the flushes at 12, 15 and 18 fall within (10, 20].
+        assert_eq!(sysdb.compaction_rate("tenant".to_string(), 10, 20), 0.3);
+        assert_eq!(sysdb.compaction_rate("other".to_string(), 10, 20), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_soft_delete_collections_by_metadata() {
+        let mut sysdb = TestSysDb::new();
+        let mut kept_id = None;
+        for (name, owner) in [("a", "cleanup"), ("b", "cleanup"), ("c", "keep")] {
+            let mut collection = test_collection("tenant", "database", name);
+            collection.metadata = Some(HashMap::from([(
+                "owner".to_string(),
+                MetadataValue::Str(owner.to_string()),
+            )]));
+            if owner == "keep" {
+                kept_id = Some(collection.collection_id);
+            }
+            sysdb.add_collection(collection);
+        }
+
+        let deleted = sysdb.soft_delete_collections_by_metadata(
+            "owner".to_string(),
+            MetadataValue::Str("cleanup".to_string()),
+        );
+        assert_eq!(deleted, 2);
+        let visible = sysdb
+            .get_collections(None, None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(visible.len(), 1);
+        assert_eq!(Some(visible[0].collection_id), kept_id);
+    }
+
+    #[tokio::test]
+    async fn test_collection_topology() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let mut record = test_segment(collection_id, SegmentScope::RECORD);
+        record.file_path =
+            HashMap::from([("a".to_string(), vec!["a/1".to_string(), "a/2".to_string()])]);
+        let metadata = test_segment(collection_id, SegmentScope::METADATA);
+        let vector = test_segment(collection_id, SegmentScope::VECTOR);
+        let expected = vec![
+            (SegmentScope::VECTOR, vector.id, 0),
+            (SegmentScope::METADATA, metadata.id, 0),
+            (SegmentScope::RECORD, record.id, 2),
+        ];
+        sysdb.add_collection(collection);
+        sysdb.add_segment(record);
+        sysdb.add_segment(metadata);
+        sysdb.add_segment(vector);
+
+        let topology = sysdb.collection_topology(collection_id).unwrap();
+        assert_eq!(topology.collection.collection_id, collection_id);
+        assert_eq!(topology.segments, expected);
+        assert!(matches!(
+            sysdb.collection_topology(CollectionUuid::new()),
+            Err(GetCollectionsError::NotFound(_))
+        ));
+
+        sysdb.delete_collection(collection_id).await.unwrap();
+        assert!(matches!(
+            sysdb.collection_topology(collection_id),
+            Err(GetCollectionsError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_flush_rejects_empty_path() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(segment);
+
+        let result = sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                10,
+                0,
+                Arc::new([SegmentFlushInfo {
+                    segment_id,
+                    file_paths: HashMap::from([(
+                        "a".to_string(),
+                        vec!["a/1".to_string(), "".to_string()],
+                    )]),
+                }]),
+                0,
+            ))
+            .await;
+        assert!(matches!(result, Err(FlushCompactionError::InvalidPath)));
+
+        let collections = sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(collections[0].version, 0);
+        assert_eq!(collections[0].log_position, 0);
+        let segments = sysdb
+            .get_segments(Some(segment_id), None, None, collection_id)
+            .await
+            .unwrap();
+        assert!(segments[0].file_path.is_empty());
+    }
+
+    #[test]
+    fn test_tenants_over_collection_limit() {
+        let mut sysdb = TestSysDb::new();
+        sysdb.add_collection(test_collection("small", "database", "collection"));
+        for i in 0..5 {
+            sysdb.add_collection(test_collection(
+                "large",
+                "database",
+                &format!("collection_{}", i),
+            ));
+        }
+
+        assert_eq!(
+            sysdb.tenants_over_collection_limit(3),
+            vec!["large".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collections_compatible() {
+        let mut sysdb = TestSysDb::new();
+        let mut ids = Vec::new();
+        for (name, dimension) in [("a", Some(3)), ("b", Some(3)), ("c", Some(4)), ("d", None)] {
+            let mut collection = test_collection("tenant", "database", name);
+            collection.dimension = dimension;
+            ids.push(collection.collection_id);
+            sysdb.add_collection(collection);
+        }
+
+        assert!(sysdb.collections_compatible(ids[0], ids[1]).unwrap());
+        assert!(!sysdb.collections_compatible(ids[0], ids[2]).unwrap());
+        assert!(sysdb.collections_compatible(ids[2], ids[3]).unwrap());
+        assert!(matches!(
+            sysdb.collections_compatible(ids[0], CollectionUuid::new()),
+            Err(GetCollectionsError::NotFound(_))
+        ));
+
+        sysdb.delete_collection(ids[1]).await.unwrap();
+        assert!(matches!(
+            sysdb.collections_compatible(ids[0], ids[1]),
+            Err(GetCollectionsError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_most_recently_compacted() {
+        let mut sysdb = TestSysDb::new();
+        let mut ids = Vec::new();
+        for name in ["a", "b", "c"] {
+            let collection = test_collection("tenant", "database", name);
+            ids.push(collection.collection_id);
+            sysdb.add_collection(collection);
+        }
+        assert_eq!(sysdb.most_recently_compacted("tenant".to_string()), None);
+
+        for (collection_id, now) in [(ids[0], 10), (ids[1], 30), (ids[2], 20)] {
+            sysdb.set_now(now);
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    10,
+                    0,
+                    Arc::new([]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            sysdb.most_recently_compacted("tenant".to_string()),
+            Some(ids[1])
+        );
+
+        sysdb.delete_collection(ids[1]).await.unwrap();
+        assert_eq!(
+            sysdb.most_recently_compacted("tenant".to_string()),
+            Some(ids[2])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gc_plan() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(segment);
+
+        // Versions 1 through 5, created at 10, 20, ..., 50, each with its own file.
+        for version in 0..5 {
+            sysdb.set_now((version as i64 + 1) * 10);
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    version as i64,
+                    version,
+                    Arc::new([SegmentFlushInfo {
+                        segment_id,
+                        file_paths: HashMap::from([(
+                            "blocks".to_string(),
+                            vec![format!("blocks/{}", version + 1)],
+                        )]),
+                    }]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+        sysdb.protect_version(collection_id, 2);
+
+        // Version 5 is kept by count, 4 is too recent and 2 is protected.
+        let plan = sysdb.gc_plan(1, 40);
+        assert_eq!(plan.collections.len(), 1);
+        assert_eq!(plan.collections[0].collection_id, collection_id);
+        assert_eq!(plan.collections[0].versions, vec![1, 3]);
+        assert_eq!(
+            plan.collections[0].unreferenced_paths,
+            vec!["blocks/1".to_string(), "blocks/3".to_string()]
+        );
+
+        // Keeping no versions by count still keeps the current one.
+        let plan = sysdb.gc_plan(0, 100);
+        assert_eq!(plan.collections[0].versions, vec![1, 3, 4]);
+        assert!(!plan.collections[0]
+            .unreferenced_paths
+            .contains(&"blocks/5".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_flush_dimension_mismatch() {
+        let mut sysdb = TestSysDb::new();
+        let mut collection = test_collection("tenant", "database", "collection");
+        collection.dimension = None;
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+
+        let mut request = FlushCompactionRequest::new(
+            "tenant".to_string(),
+            collection_id,
+            10,
+            0,
+            Arc::new([]),
+            0,
+        );
+        request.dimension = Some(3);
+        sysdb.flush(request.clone()).await.unwrap();
+        let collections = sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(collections[0].dimension, Some(3));
+
+        request.collection_version = 1;
+        request.dimension = Some(4);
+        assert!(matches!(
+            sysdb.flush(request).await,
+            Err(FlushCompactionError::DimensionMismatch {
+                current: 3,
+                requested: 4
+            })
+        ));
+        let collections = sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(collections[0].dimension, Some(3));
+        assert_eq!(collections[0].version, 1);
+    }
+
+    #[test]
+    fn test_collections_by_version() {
+        let mut sysdb = TestSysDb::new();
+        let mut at_one = Vec::new();
+        let mut at_two = Vec::new();
+        for (name, version) in [("a", 1), ("b", 1), ("c", 2)] {
+            let mut collection = test_collection("tenant", "database", name);
+            collection.version = version;
+            if version == 1 {
+                at_one.push(collection.collection_id);
+            } else {
+                at_two.push(collection.collection_id);
+            }
+            sysdb.add_collection(collection);
+        }
+        at_one.sort();
+        let mut other = test_collection("other", "database", "d");
+        other.version = 3;
+        sysdb.add_collection(other);
+
+        let by_version = sysdb.collections_by_version(Some("tenant".to_string()));
+        assert_eq!(by_version.len(), 2);
+        assert_eq!(by_version[&1], at_one);
+        assert_eq!(by_version[&2], at_two);
+        assert_eq!(sysdb.collections_by_version(None).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_flush_validator() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        sysdb.set_flush_validator(Arc::new(|request: &FlushCompactionRequest| {
+            if request.total_records_post_compaction > 100 {
+                return Err("too many records".to_string());
+            }
+            Ok(())
+        }));
+
+        let mut request = FlushCompactionRequest::new(
+            "tenant".to_string(),
+            collection_id,
+            10,
+            0,
+            Arc::new([]),
+            101,
+        );
+        let result = sysdb.flush(request.clone()).await;
+        assert!(
+            matches!(result, Err(FlushCompactionError::ValidationFailed(reason)) if reason == "too many records")
+        );
+
+        request.total_records_post_compaction = 100;
+        assert!(sysdb.flush(request).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_flush_validator_skips_retried_flush() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        let mut request = FlushCompactionRequest::new(
+            "tenant".to_string(),
+            collection_id,
+            10,
+            0,
+            Arc::new([]),
+            0,
+        );
+        request.idempotency_key = Some("flush-1".to_string());
+        let first = sysdb.flush(request.clone()).await.unwrap();
+
+        sysdb.set_flush_validator(Arc::new(|_: &FlushCompactionRequest| {
+            Err("closed".to_string())
+        }));
+        let retried = sysdb.flush(request).await.unwrap();
+        assert_eq!(retried.collection_version, first.collection_version);
+    }
+
+    #[tokio::test]
+    async fn test_list_collection_versions() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(segment);
+        for version in 0..2 {
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    version as i64 + 1,
+                    version,
+                    Arc::new([SegmentFlushInfo {
+                        segment_id,
+                        file_paths: HashMap::from([(
+                            "blocks".to_string(),
+                            vec![format!("blocks/{}", version + 1)],
+                        )]),
+                    }]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+
+        let versions = SysDb::Test(sysdb)
+            .list_collection_versions(collection_id, "tenant".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            versions.iter().map(|info| info.version).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        for info in versions {
+            let segments = info.segment_info.unwrap().segment_compaction_info;
+            assert_eq!(segments.len(), 1);
+            assert_eq!(segments[0].segment_id, segment_id.to_string());
+            assert_eq!(
+                segments[0].file_paths["blocks"].paths,
+                vec![format!("blocks/{}", info.version)]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_segments_at_version() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(segment);
+
+        for version in 0..2 {
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    version as i64,
+                    version,
+                    Arc::new([SegmentFlushInfo {
+                        segment_id,
+                        file_paths: HashMap::from([(
+                            "blocks".to_string(),
+                            vec![format!("blocks/{}", version + 1)],
+                        )]),
+                    }]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+
+        let earlier = sysdb.segments_at_version(collection_id, 1).unwrap();
+        assert_eq!(earlier.len(), 1);
+        assert_eq!(earlier[0].file_path["blocks"], vec!["blocks/1".to_string()]);
+        let later = sysdb.segments_at_version(collection_id, 2).unwrap();
+        assert_eq!(later[0].file_path["blocks"], vec!["blocks/2".to_string()]);
+        assert!(matches!(
+            sysdb.segments_at_version(collection_id, 3),
+            Err(GetSegmentsError::VersionNotFound(3))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_take_compaction_log() {
+        let mut sysdb = TestSysDb::new();
+        let first = test_collection("tenant", "database", "first");
+        let first_id = first.collection_id;
+        let second = test_collection("tenant", "database", "second");
+        let second_id = second.collection_id;
+        sysdb.add_collection(first);
+        sysdb.add_collection(second);
+        let flush = |collection_id, log_position, version| {
+            FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                log_position,
+                version,
+                Arc::new([]),
+                log_position as u64 * 10,
+            )
+        };
+        sysdb.flush(flush(first_id, 1, 0)).await.unwrap();
+        sysdb.flush(flush(second_id, 2, 0)).await.unwrap();
+        // Rejected flushes are not logged.
+        assert!(sysdb.flush(flush(first_id, 3, 0)).await.is_err());
+        sysdb.flush(flush(first_id, 4, 1)).await.unwrap();
+
+        let record = |collection_id, log_position, collection_version| CompactionRecord {
+            tenant_id: "tenant".to_string(),
+            collection_id,
+            log_position,
+            collection_version,
+            total_records_post_compaction: log_position as u64 * 10,
+        };
+        assert_eq!(
+            sysdb.take_compaction_log(),
+            vec![
+                record(first_id, 1, 1),
+                record(second_id, 2, 1),
+                record(first_id, 4, 2),
+            ]
+        );
+        assert!(sysdb.take_compaction_log().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fail_next() {
+        let mut sysdb = TestSysDb::new();
+        let collection_id = flushed_collection(&mut sysdb, 1).await;
+        sysdb.add_tenant_last_compaction_time("tenant".to_string(), 1);
+        sysdb.fail_next(InjectedError::FlushCompaction(
+            FlushCompactionError::CollectionNotFound,
+        ));
+        sysdb.fail_next(InjectedError::GetCollections(
+            GetCollectionsError::NotFound(collection_id.to_string()),
+        ));
+        sysdb.fail_next(InjectedError::GetSegments(GetSegmentsError::Unavailable));
+        sysdb.fail_next(InjectedError::GetLastCompactionTime(
+            GetLastCompactionTimeError::TenantNotFound,
+        ));
+        // A deep clone starts without the queued errors.
+        let mut copy = sysdb.deep_clone();
+        assert!(copy
+            .flush_compaction("tenant".to_string(), collection_id, 2, 1, Arc::new([]), 0)
+            .await
+            .is_ok());
+
+        let flush = |version| {
+            FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                2,
+                version,
+                Arc::new([]),
+                0,
+            )
+        };
+        assert!(matches!(
+            sysdb.flush(flush(1)).await,
+            Err(FlushCompactionError::CollectionNotFound)
+        ));
+        assert!(sysdb.flush(flush(1)).await.is_ok());
+
+        assert!(matches!(
+            sysdb.get_collections(None, None, None, None, None, 0).await,
+            Err(GetCollectionsError::NotFound(_))
+        ));
+        assert!(sysdb
+            .get_collections(None, None, None, None, None, 0)
+            .await
+            .is_ok());
+        assert!(matches!(
+            sysdb.get_segments(None, None, None, collection_id).await,
+            Err(GetSegmentsError::Unavailable)
+        ));
+        assert!(sysdb
+            .get_segments(None, None, None, collection_id)
+            .await
+            .is_ok());
+        assert!(matches!(
+            sysdb
+                .get_last_compaction_time(vec!["tenant".to_string()])
+                .await,
+            Err(GetLastCompactionTimeError::TenantNotFound)
+        ));
+        assert!(sysdb
+            .get_last_compaction_time(vec!["tenant".to_string()])
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_flush_rejects_stale_version() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+
+        // Two compactions both read version 0; only the first may land.
+        let flush = |log_position| {
+            FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                log_position,
+                0,
+                Arc::new([]),
+                0,
+            )
+        };
+        let response = sysdb.flush(flush(10)).await.unwrap();
+        assert_eq!(response.collection_version, 1);
+        assert!(matches!(
+            sysdb.flush(flush(20)).await,
+            Err(FlushCompactionError::VersionMismatch)
+        ));
+
+        let collection = sysdb.find_collections(&CollectionFilter::default());
+        assert_eq!(collection[0].log_position, 10);
+        assert_eq!(collection[0].version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_lenient_version_check_flag() {
+        let mut sysdb = TestSysDb::new();
+        let strict = test_collection("strict", "database", "collection");
+        let strict_id = strict.collection_id;
+        let lenient = test_collection("lenient", "database", "collection");
+        let lenient_id = lenient.collection_id;
+        sysdb.add_collection(strict);
+        sysdb.add_collection(lenient);
+        sysdb.set_tenant_flag(
+            "lenient".to_string(),
+            LENIENT_VERSION_CHECK_FLAG.to_string(),
+        );
+        assert!(sysdb.has_tenant_flag(
+            "lenient".to_string(),
+            LENIENT_VERSION_CHECK_FLAG.to_string()
+        ));
+        assert!(
+            !sysdb.has_tenant_flag("strict".to_string(), LENIENT_VERSION_CHECK_FLAG.to_string())
+        );
+
+        // Both collections are at version 0, so version 5 is stale.
+        let stale = |tenant: &str, collection_id| {
+            FlushCompactionRequest::new(tenant.to_string(), collection_id, 10, 5, Arc::new([]), 0)
+        };
+        assert!(matches!(
+            sysdb.flush(stale("strict", strict_id)).await,
+            Err(FlushCompactionError::VersionMismatch)
+        ));
+        assert!(sysdb.flush(stale("lenient", lenient_id)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_flush_rejects_segment_of_other_collection() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let other = test_collection("tenant", "database", "other");
+        let collection_id = collection.collection_id;
+        let other_id = other.collection_id;
+        let other_segment = test_segment(other_id, SegmentScope::RECORD);
+        let other_segment_id = other_segment.id;
+        sysdb.add_collection(collection);
+        sysdb.add_collection(other);
+        sysdb.add_segment(other_segment);
+
+        let result = sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                10,
+                0,
+                Arc::new([SegmentFlushInfo {
+                    segment_id: other_segment_id,
+                    file_paths: HashMap::from([(
+                        "blocks".to_string(),
+                        vec!["blocks/1".to_string()],
+                    )]),
+                }]),
+                0,
+            ))
+            .await;
+        assert!(matches!(result, Err(FlushCompactionError::SegmentNotFound)));
+
+        let collection = sysdb.find_collections(&CollectionFilter {
+            collection_id: Some(collection_id),
+            ..Default::default()
+        });
+        assert_eq!(collection[0].log_position, 0);
+        assert_eq!(collection[0].version, 0);
+        assert!(sysdb.get_collection_versions(collection_id).is_empty());
+        let segments = sysdb
+            .get_segments(Some(other_segment_id), None, None, other_id)
+            .await
+            .unwrap();
+        assert!(segments[0].file_path.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_deletable_paths() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(segment);
+
+        // Versions 1 and 2 both reference blocks/shared.
+        let layouts = [
+            vec!["blocks/1", "blocks/shared"],
+            vec!["blocks/shared", "blocks/2"],
+            vec!["blocks/3"],
+        ];
+        for (version, layout) in layouts.iter().enumerate() {
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    version as i64,
+                    version as i32,
+                    Arc::new([SegmentFlushInfo {
+                        segment_id,
+                        file_paths: HashMap::from([(
+                            "blocks".to_string(),
+                            layout.iter().map(|path| path.to_string()).collect(),
+                        )]),
+                    }]),
+                    0,
+                ))
+                .await
+                .unwrap();
+        }
+
+        // Only version 1 is deletable; version 2 survives and still uses blocks/shared.
+        let plan = GcPlan {
+            collections: vec![CollectionGcPlan {
+                collection_id,
+                versions: vec![1],
+                unreferenced_paths: Vec::new(),
+            }],
+        };
+        assert_eq!(sysdb.deletable_paths(&plan), vec!["blocks/1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_pending_first_compaction() {
+        let mut sysdb = TestSysDb::new();
+        let fresh = test_collection("tenant", "database", "fresh");
+        let fresh_id = fresh.collection_id;
+        let compacted = test_collection("tenant", "database", "compacted");
+        let compacted_id = compacted.collection_id;
+        sysdb.add_collection(fresh);
+        sysdb.add_collection(compacted);
+        sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                compacted_id,
+                10,
+                0,
+                Arc::new([]),
+                0,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sysdb.pending_first_compaction("tenant".to_string()),
+            vec![fresh_id]
+        );
+        assert!(sysdb
+            .pending_first_compaction("other".to_string())
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apply_segment_delta() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let old = test_segment(collection_id, SegmentScope::VECTOR);
+        let old_id = old.id;
+        let kept = test_segment(collection_id, SegmentScope::RECORD);
+        let kept_id = kept.id;
+        let new = test_segment(collection_id, SegmentScope::VECTOR);
+        let new_id = new.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(old);
+        sysdb.add_segment(kept);
+
+        sysdb
+            .apply_segment_delta(collection_id, vec![old_id], vec![new])
+            .unwrap();
+
+        let mut segment_ids = sysdb
+            .get_segments(None, None, None, collection_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|segment| segment.id)
+            .collect::<Vec<_>>();
+        segment_ids.sort();
+        let mut expected = vec![kept_id, new_id];
+        expected.sort();
+        assert_eq!(segment_ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_apply_segment_delta_bad_addition() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let old = test_segment(collection_id, SegmentScope::VECTOR);
+        let old_id = old.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(old);
+
+        let foreign = test_segment(CollectionUuid::new(), SegmentScope::VECTOR);
+        let result = sysdb.apply_segment_delta(collection_id, vec![old_id], vec![foreign]);
+        assert!(matches!(result, Err(SegmentDeltaError::WrongCollection(_))));
+
+        let segments = sysdb
+            .get_segments(None, None, None, collection_id)
+            .await
+            .unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].id, old_id);
+    }
+
+    #[tokio::test]
+    async fn test_apply_segment_delta_rejects_segment_of_other_collection() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let other = test_collection("tenant", "database", "other");
+        let other_id = other.collection_id;
+        let old = test_segment(collection_id, SegmentScope::VECTOR);
+        let old_id = old.id;
+        let taken = test_segment(other_id, SegmentScope::VECTOR);
+        sysdb.add_collection(collection);
+        sysdb.add_collection(other);
+        sysdb.add_segment(old);
+        sysdb.add_segment(taken.clone());
+
+        // Same id as the other collection's segment, relabelled as ours.
+        let stolen = Segment {
+            collection: collection_id,
+            ..taken.clone()
+        };
+        let result = sysdb.apply_segment_delta(collection_id, vec![old_id], vec![stolen]);
+        assert!(matches!(
+            result,
+            Err(SegmentDeltaError::WrongCollection(id)) if id == taken.id
+        ));
+
+        let segments = sysdb
+            .get_segments(None, None, None, collection_id)
+            .await
+            .unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].id, old_id);
+        let other_segments = sysdb
+            .get_segments(None, None, None, other_id)
+            .await
+            .unwrap();
+        assert_eq!(other_segments.len(), 1);
+        assert_eq!(other_segments[0].id, taken.id);
+    }
+
+    #[tokio::test]
+    async fn test_oldest_uncompacted_age() {
+        let mut sysdb = TestSysDb::new();
+        let mut ids = Vec::new();
+        for name in ["a", "b", "c"] {
+            let collection = test_collection("tenant", "database", name);
+            ids.push(collection.collection_id);
+            sysdb.add_collection(collection);
+        }
+        assert_eq!(
+            sysdb.oldest_uncompacted_age("tenant".to_string(), 100),
+            None
+        );
+
+        // a lags from 60, b from 30; c is fully compacted.
+        sysdb.append_log(ids[0], 1, 60);
+        sysdb.append_log(ids[0], 2, 70);
+        sysdb.append_log(ids[1], 1, 30);
+        sysdb.append_log(ids[1], 2, 80);
+        sysdb.append_log(ids[2], 1, 10);
+        sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                ids[2],
+                1,
+                0,
+                Arc::new([]),
+                0,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            sysdb.oldest_uncompacted_age("tenant".to_string(), 100),
+            Some(70)
+        );
+
+        sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                ids[1],
+                1,
+                0,
+                Arc::new([]),
+                0,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            sysdb.oldest_uncompacted_age("tenant".to_string(), 100),
+            Some(40)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deep_clone() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+
+        let mut copy = sysdb.deep_clone();
+        copy.add_collection(test_collection("tenant", "database", "other"));
+        copy.update_collection_size(collection_id, 100);
+
+        let original = sysdb
+            .get_collections(None, None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(original.len(), 1);
+        assert_eq!(original[0].total_records_post_compaction, 0);
+        assert_eq!(
+            copy.get_collections(None, None, None, None, None, 0)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flushes_for_job() {
+        let mut sysdb = TestSysDb::new();
+        let mut expected = Vec::new();
+        for (name, job_id) in [("a", "job-1"), ("b", "job-1"), ("c", "job-2")] {
+            let collection = test_collection("tenant", "database", name);
+            let collection_id = collection.collection_id;
+            sysdb.add_collection(collection);
+            let mut request = FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                10,
+                0,
+                Arc::new([]),
+                0,
+            );
+            request.job_id = Some(job_id.to_string());
+            sysdb.flush(request).await.unwrap();
+            if job_id == "job-1" {
+                expected.push((collection_id, 1));
+            }
+        }
+        expected.sort();
+
+        assert_eq!(sysdb.flushes_for_job("job-1".to_string()), expected);
+        assert!(sysdb.flushes_for_job("job-3".to_string()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_collection_database_tenant_mismatch() {
+        let mut sysdb = TestSysDb::new();
+        let database_id = uuid::Uuid::new_v4();
+        sysdb
+            .create_database(database_id, "database".to_string(), "tenant_a".to_string())
+            .await
+            .unwrap();
+
+        let result = sysdb
+            .create_collection(
+                test_collection("tenant_b", &database_id.to_string(), "b"),
+                Vec::new(),
+                false,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(CreateCollectionError::DatabaseTenantMismatch(_))
+        ));
+        assert!(sysdb
+            .create_collection(
+                test_collection("tenant_a", "database", "c"),
+                Vec::new(),
+                false
+            )
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_databases_are_keyed_by_id() {
+        let mut sysdb = TestSysDb::new();
+        let database_id = uuid::Uuid::new_v4();
+        sysdb
+            .create_database(database_id, "database".to_string(), "tenant_a".to_string())
+            .await
+            .unwrap();
+        // The same name is free in another tenant, the same id is not.
+        sysdb
+            .create_database(
+                uuid::Uuid::new_v4(),
+                "database".to_string(),
+                "tenant_b".to_string(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            sysdb
+                .create_database(database_id, "other".to_string(), "tenant_b".to_string())
+                .await,
+            Err(CreateDatabaseError::AlreadyExists(_))
+        ));
+
+        sysdb
+            .delete_database("database".to_string(), "tenant_a".to_string())
+            .await
+            .unwrap();
+        assert!(sysdb
+            .get_database("database".to_string(), "tenant_a".to_string())
+            .await
+            .is_err());
+        let tenant_b = sysdb
+            .get_database("database".to_string(), "tenant_b".to_string())
+            .await
+            .unwrap();
+        assert_ne!(tenant_b.id, database_id);
+        // A deleted database's id may be used again.
+        sysdb
+            .create_database(database_id, "other".to_string(), "tenant_b".to_string())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_collection_in_default_database_of_each_tenant() {
+        let mut sysdb = TestSysDb::new();
+        for tenant in ["tenant_a", "tenant_b"] {
+            sysdb.create_tenant(tenant.to_string()).await.unwrap();
+        }
+
+        for tenant in ["tenant_a", "tenant_b"] {
+            sysdb
+                .create_collection(
+                    test_collection(tenant, "default_database", "collection"),
+                    Vec::new(),
+                    false,
+                )
+                .await
+                .unwrap();
+        }
+        // A tenant that was never created can use the name too.
+        sysdb
+            .create_collection(
+                test_collection("tenant_c", "default_database", "collection"),
+                Vec::new(),
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(sysdb.count_collections(None, None).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_create_collection_with_segments_is_atomic() {
+        let mut sysdb = TestSysDb::new();
+        let existing = test_segment(CollectionUuid::new(), SegmentScope::VECTOR);
+        sysdb.add_segment(existing.clone());
+        let snapshot = sysdb.snapshot();
+        let calls = sysdb.call_log().len();
+
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segments = vec![
+            test_segment(collection_id, SegmentScope::METADATA),
+            Segment {
+                collection: collection_id,
+                ..existing
+            },
+            test_segment(collection_id, SegmentScope::RECORD),
+        ];
+        let result = sysdb.create_collection_with_segments(collection.clone(), segments);
+        assert!(matches!(result, Err(CreateCollectionError::Internal(_))));
+        assert!(sysdb
+            .find_collections(&CollectionFilter::default())
+            .is_empty());
+        assert_eq!(sysdb.count_segments(collection_id).await.unwrap(), 0);
+        assert_eq!(sysdb.snapshot(), snapshot);
+        assert_eq!(sysdb.call_log().len(), calls);
+
+        let segments = [
+            SegmentScope::METADATA,
+            SegmentScope::RECORD,
+            SegmentScope::VECTOR,
+        ]
+        .into_iter()
+        .map(|scope| test_segment(collection_id, scope))
+        .collect();
+        sysdb
+            .create_collection_with_segments(collection, segments)
+            .unwrap();
+        assert_eq!(sysdb.count_segments(collection_id).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_create_collection_duplicate_name() {
+        let mut sysdb = TestSysDb::new();
+        let original = sysdb
+            .create_collection(
+                test_collection("tenant", "database", "a"),
+                Vec::new(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        let result = sysdb
+            .create_collection(
+                test_collection("tenant", "database", "a"),
+                Vec::new(),
+                false,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(CreateCollectionError::AlreadyExists(_))
+        ));
+
+        let existing = sysdb
+            .create_collection(test_collection("tenant", "database", "a"), Vec::new(), true)
+            .await
+            .unwrap();
+        assert_eq!(existing.collection_id, original.collection_id);
+        assert_eq!(
+            sysdb.find_collections(&CollectionFilter::default()).len(),
+            1
+        );
+
+        // The same name is free in another database.
+        assert!(sysdb
+            .create_collection(test_collection("tenant", "other", "a"), Vec::new(), false)
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_flush_orphaned_paths() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let mut record = test_segment(collection_id, SegmentScope::RECORD);
+        record.file_path = HashMap::from([(
+            "blocks".to_string(),
+            vec!["blocks/1".to_string(), "blocks/2".to_string()],
+        )]);
+        let record_id = record.id;
+        let mut vector = test_segment(collection_id, SegmentScope::VECTOR);
+        vector.file_path = HashMap::from([("hnsw".to_string(), vec!["hnsw/1".to_string()])]);
+        sysdb.add_collection(collection);
+        sysdb.add_segment(record);
+        sysdb.add_segment(vector);
+
+        let flush_info = [SegmentFlushInfo {
+            segment_id: record_id,
+            file_paths: HashMap::from([(
+                "blocks".to_string(),
+                vec!["blocks/2".to_string(), "blocks/3".to_string()],
+            )]),
+        }];
+        assert_eq!(
+            sysdb
+                .flush_orphaned_paths(collection_id, &flush_info)
+                .unwrap(),
+            vec!["blocks/1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_collections_with_tenant_compaction_time() {
+        let mut sysdb = TestSysDb::new();
+        sysdb.add_collection(test_collection("compacted", "database", "a"));
+        sysdb.add_collection(test_collection("compacted", "database", "b"));
+        sysdb.add_collection(test_collection("unknown", "database", "c"));
+        sysdb.add_tenant_last_compaction_time("compacted".to_string(), 42);
+
+        let joined = sysdb.collections_with_tenant_compaction_time("compacted".to_string());
+        assert_eq!(joined.len(), 2);
+        assert!(joined
+            .iter()
+            .all(|(collection, time)| collection.tenant == "compacted" && *time == 42));
+        let joined = sysdb.collections_with_tenant_compaction_time("unknown".to_string());
+        assert_eq!(joined.len(), 1);
+        assert_eq!(joined[0].1, 0);
+    }
+
+    #[test]
+    fn test_cas_bump_version() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+
+        assert_eq!(sysdb.cas_bump_version(collection_id, 0).unwrap(), 1);
+        assert!(matches!(
+            sysdb.cas_bump_version(collection_id, 0),
+            Err(CasError::Mismatch {
+                expected: 0,
+                actual: 1
+            })
+        ));
+        assert_eq!(sysdb.cas_bump_version(collection_id, 1).unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_restore_from_snapshot() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        let mut snapshot = CollectionSnapshot {
+            collection: collection.clone(),
+            segments: vec![segment.clone()],
+            versions: vec![VersionRecord {
+                version: 0,
+                collection,
+                created_at: 0,
+                segments: vec![segment],
+                job_id: None,
+                marked_for_deletion: false,
+            }],
+            overwrite: false,
+        };
+
+        sysdb.restore_from_snapshot(snapshot.clone()).unwrap();
+        let collections = sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(collections.len(), 1);
+        let segments = sysdb
+            .get_segments(None, None, None, collection_id)
+            .await
+            .unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].id, segment_id);
+        assert!(sysdb.get_collection_at_version(collection_id, 0).is_ok());
+
+        assert!(matches!(
+            sysdb.restore_from_snapshot(snapshot.clone()),
+            Err(RestoreError::AlreadyExists(_))
+        ));
+        snapshot.overwrite = true;
+        assert!(sysdb.restore_from_snapshot(snapshot).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_restore_from_snapshot_replaces_segments() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let replaced = test_segment(collection_id, SegmentScope::RECORD);
+        let replaced_id = replaced.id;
+        sysdb.add_collection(collection.clone());
+        sysdb.add_segment(replaced);
+
+        let restored = test_segment(collection_id, SegmentScope::RECORD);
+        let restored_id = restored.id;
+        sysdb
+            .restore_from_snapshot(CollectionSnapshot {
+                collection,
+                segments: vec![restored],
+                versions: vec![],
+                overwrite: true,
+            })
+            .unwrap();
+
+        let segments = sysdb
+            .get_segments(None, None, None, collection_id)
+            .await
+            .unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].id, restored_id);
+        let inner = sysdb.inner.lock();
+        assert!(!inner.segment_updated_at.contains_key(&replaced_id));
+        assert!(inner.segment_updated_at.contains_key(&restored_id));
+    }
+
+    #[test]
+    fn test_fragmented_collections() {
+        let mut sysdb = TestSysDb::new();
+        let fragmented = test_collection("tenant", "database", "fragmented");
+        let fragmented_id = fragmented.collection_id;
+        let tidy = test_collection("tenant", "database", "tidy");
+        let tidy_id = tidy.collection_id;
+        sysdb.add_collection(fragmented);
+        sysdb.add_collection(tidy);
+
+        let mut segment = test_segment(fragmented_id, SegmentScope::RECORD);
+        segment.file_path = HashMap::from([(
+            "blocks".to_string(),
+            (0..10).map(|i| format!("blocks/{}", i)).collect(),
+        )]);
+        sysdb.add_segment(segment);
+        let mut segment = test_segment(tidy_id, SegmentScope::RECORD);
+        segment.file_path = HashMap::from([("blocks".to_string(), vec!["blocks/0".to_string()])]);
+        sysdb.add_segment(segment);
+
+        assert_eq!(sysdb.fragmented_collections(4), vec![fragmented_id]);
+    }
+
+    #[test]
+    fn test_segment_path_diff() {
+        let mut sysdb = TestSysDb::new();
+        let parent = test_collection("tenant", "database", "parent");
+        let parent_id = parent.collection_id;
+        let mut segment = test_segment(parent_id, SegmentScope::RECORD);
+        segment.file_path = HashMap::from([(
+            "blocks".to_string(),
+            vec!["blocks/1".to_string(), "blocks/2".to_string()],
+        )]);
+        sysdb.add_collection(parent);
+        sysdb.add_segment(segment.clone());
+
+        // A fork starts out pointing at its parent's files.
+        let fork = test_collection("tenant", "database", "fork");
+        let fork_id = fork.collection_id;
+        sysdb.add_collection(fork);
+        sysdb.add_segment(Segment {
+            id: SegmentUuid::new(),
+            collection: fork_id,
+            ..segment
+        });
+
+        assert_eq!(
+            sysdb.segment_path_diff(parent_id, fork_id).unwrap(),
+            SegmentPathDiff {
+                only_a: Vec::new(),
+                only_b: Vec::new(),
+                shared: vec!["blocks/1".to_string(), "blocks/2".to_string()],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_total_versions() {
+        let mut sysdb = TestSysDb::new();
+        assert_eq!(sysdb.total_versions(), 0);
+        for (name, flushes) in [("a", 2), ("b", 3)] {
+            let collection = test_collection("tenant", "database", name);
+            let collection_id = collection.collection_id;
+            sysdb.add_collection(collection);
+            for version in 0..flushes {
+                sysdb
+                    .flush(FlushCompactionRequest::new(
+                        "tenant".to_string(),
+                        collection_id,
+                        version as i64,
+                        version,
+                        Arc::new([]),
+                        0,
+                    ))
+                    .await
+                    .unwrap();
+            }
+        }
+
+        assert_eq!(sysdb.total_versions(), 5);
+    }
+
+    #[test]
+    fn test_collections_sharing_paths() {
+        let mut sysdb = TestSysDb::new();
+        let mut ids = Vec::new();
+        for (name, paths) in [
+            ("a", vec!["a/1", "shared/1"]),
+            ("b", vec!["b/1", "shared/1"]),
+            ("c", vec!["c/1"]),
+        ] {
+            let collection = test_collection("tenant", "database", name);
+            let mut segment = test_segment(collection.collection_id, SegmentScope::RECORD);
+            segment.file_path = HashMap::from([(
+                "blocks".to_string(),
+                paths.into_iter().map(|path| path.to_string()).collect(),
+            )]);
+            ids.push(collection.collection_id);
+            sysdb.add_collection(collection);
+            sysdb.add_segment(segment);
+        }
+        let mut sharing = vec![ids[0], ids[1]];
+        sharing.sort();
+
+        assert_eq!(
+            sysdb.collections_sharing_paths(),
+            vec![("shared/1".to_string(), sharing)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compaction_cooldown() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        sysdb.set_compaction_cooldown(Duration::from_secs(60));
+        assert_eq!(sysdb.next_compaction_candidate(), Some(collection_id));
+
+        sysdb.set_now(100);
+        sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                10,
+                0,
+                Arc::new([]),
+                0,
+            ))
+            .await
+            .unwrap();
+        sysdb.set_now(159);
+        assert_eq!(sysdb.next_compaction_candidate(), None);
+
+        sysdb.set_now(160);
+        assert_eq!(sysdb.next_compaction_candidate(), Some(collection_id));
+    }
+
+    #[tokio::test]
+    async fn test_collections_with_stale_version_file() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        sysdb.set_version_file(collection_id, "versions/0".to_string(), 0);
+        assert!(sysdb.collections_with_stale_version_file().is_empty());
+
+        sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                10,
+                0,
+                Arc::new([]),
+                0,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            sysdb.collections_with_stale_version_file(),
+            vec![collection_id]
+        );
+
+        sysdb.set_version_file(collection_id, "versions/1".to_string(), 1);
+        assert!(sysdb.collections_with_stale_version_file().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_latency_and_repeated_failures() {
+        tokio::time::pause();
+        let mut sysdb = TestSysDb::new();
+        let collection_id = flushed_collection(&mut sysdb, 1).await;
+        sysdb.set_latency(SysDbMethod::GetSegments, Duration::from_secs(10));
+        sysdb.fail_times(2, || {
+            InjectedError::GetSegments(GetSegmentsError::Unavailable)
+        });
+
+        let done = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut reader = sysdb.clone();
+        let reader_done = done.clone();
+        let read = tokio::spawn(async move {
+            let mut results = Vec::new();
+            for _ in 0..3 {
+                results.push(reader.get_segments(None, None, None, collection_id).await);
+                reader_done.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+            results
+        });
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(done.load(std::sync::atomic::Ordering::SeqCst), 0);
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(done.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let results = read.await.unwrap();
+        assert!(matches!(results[0], Err(GetSegmentsError::Unavailable)));
+        assert!(matches!(results[1], Err(GetSegmentsError::Unavailable)));
+        assert!(results[2].is_ok());
+
+        sysdb.set_latency(SysDbMethod::GetSegments, Duration::ZERO);
+        assert!(sysdb
+            .get_segments(None, None, None, collection_id)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_flush_pause() {
+        tokio::time::pause();
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(segment);
+        sysdb.set_flush_pause(Duration::from_secs(10));
+
+        let mut flusher = sysdb.clone();
+        let flush = tokio::spawn(async move {
+            flusher
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    10,
+                    0,
+                    Arc::new([SegmentFlushInfo {
+                        segment_id,
+                        file_paths: HashMap::from([(
+                            "blocks".to_string(),
+                            vec!["blocks/1".to_string()],
+                        )]),
+                    }]),
+                    0,
+                ))
+                .await
+        });
+        tokio::time::sleep(Duration::from_secs(5)).await;
+
+        let collections = sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(collections[0].version, 1);
+        let segments = sysdb
+            .get_segments(Some(segment_id), None, None, collection_id)
+            .await
+            .unwrap();
+        assert!(segments[0].file_path.is_empty());
+
+        flush.await.unwrap().unwrap();
+        let segments = sysdb
+            .get_segments(Some(segment_id), None, None, collection_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            segments[0].file_path["blocks"],
+            vec!["blocks/1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_full_manifest() {
+        let mut sysdb = TestSysDb::new();
+        let mut collection = test_collection("tenant", "database", "collection");
+        collection.version = 3;
+        let collection_id = collection.collection_id;
+        let mut segment = test_segment(collection_id, SegmentScope::RECORD);
+        segment.file_path = HashMap::from([("blocks".to_string(), vec!["blocks/1".to_string()])]);
+        let segment_id = segment.id;
+        let empty = test_segment(collection_id, SegmentScope::METADATA);
+        let empty_id = empty.id;
+        sysdb.add_collection(collection);
+        sysdb.add_segment(segment);
+        sysdb.add_segment(empty);
+        sysdb.add_collection(test_collection("other", "database", "collection"));
+
+        assert_eq!(
+            sysdb.full_manifest(Some("tenant".to_string())),
+            vec![CollectionManifest {
+                collection_id,
+                version: 3,
+                segments: BTreeMap::from([
+                    (
+                        segment_id,
+                        HashMap::from([("blocks".to_string(), vec!["blocks/1".to_string()])]),
+                    ),
+                    (empty_id, HashMap::new()),
+                ]),
+            }]
+        );
+        assert_eq!(sysdb.full_manifest(None).len(), 2);
+    }
+
+    #[test]
+    fn test_collections_with_changed_tenant() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "moved");
+        let moved_id = collection.collection_id;
+        sysdb.add_collection(collection.clone());
+        sysdb.add_collection(test_collection("tenant", "database", "stayed"));
+        assert!(sysdb.collections_with_changed_tenant().is_empty());
+
+        sysdb.add_collection(Collection {
+            tenant: "other".to_string(),
+            ..collection
+        });
+        assert_eq!(sysdb.collections_with_changed_tenant(), vec![moved_id]);
+    }
+
+    #[tokio::test]
+    async fn test_get_segments_by_type_and_collection() {
+        let mut sysdb = TestSysDb::new();
+        let collection_a = CollectionUuid::new();
+        let collection_b = CollectionUuid::new();
+        let segment_a = test_segment(collection_a, SegmentScope::RECORD);
+        let segment_a_id = segment_a.id;
+        sysdb.add_segment(segment_a);
+        sysdb.add_segment(test_segment(collection_b, SegmentScope::RECORD));
+        let record_type = String::from(SegmentType::BlockfileRecord);
+
+        let segments = sysdb
+            .get_segments(None, Some(record_type.clone()), None, collection_a)
+            .await
+            .unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].id, segment_a_id);
+
+        let segments = sysdb
+            .get_segments(
+                None,
+                Some(record_type),
+                Some(SegmentScope::VECTOR),
+                collection_a,
+            )
+            .await
+            .unwrap();
+        assert!(segments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_stats() {
+        let mut sysdb = TestSysDb::new();
+        let mut ids = Vec::new();
+        for (tenant, database, name) in [
+            ("tenant_1", "database_1", "a"),
+            ("tenant_1", "database_2", "b"),
+            ("tenant_2", "database_1", "c"),
+        ] {
+            let collection = test_collection(tenant, database, name);
+            sysdb.add_segment(test_segment(collection.collection_id, SegmentScope::RECORD));
+            ids.push(collection.collection_id);
+            sysdb.add_collection(collection);
+        }
+        sysdb.add_segment(test_segment(ids[0], SegmentScope::VECTOR));
+        for (i, collection_id) in ids.iter().take(2).enumerate() {
+            sysdb
+                .flush(FlushCompactionRequest::new(
+                    "tenant_1".to_string(),
+                    *collection_id,
+                    10,
+                    0,
+                    Arc::new([]),
+                    (i as u64 + 1) * 100,
+                ))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            sysdb.aggregate_stats(),
+            AggregateStats {
+                total_collections: 3,
+                total_segments: 4,
+                total_records: 300,
+                total_versions: 2,
+                distinct_tenants: 2,
+                distinct_databases: 3,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_databases_offset() {
+        let mut sysdb = TestSysDb::new();
+        for database in ["d", "b", "a", "c", "b"] {
+            sysdb.add_collection(test_collection(
+                "tenant",
+                database,
+                &format!("collection_{}", database),
+            ));
+        }
+        let names = |databases: ListDatabasesResponse| {
+            databases
+                .into_iter()
+                .map(|database| database.name)
+                .collect::<Vec<_>>()
+        };
+
+        let first = sysdb
+            .list_databases("tenant".to_string(), Some(2), 0)
+            .await
+            .unwrap();
+        assert_eq!(names(first), vec!["a", "b"]);
+        let second = sysdb
+            .list_databases("tenant".to_string(), Some(2), 2)
+            .await
+            .unwrap();
+        assert_eq!(names(second), vec!["c", "d"]);
+        let rest = sysdb
+            .list_databases("tenant".to_string(), None, 1)
+            .await
+            .unwrap();
+        assert_eq!(names(rest), vec!["b", "c", "d"]);
+        let past_end = sysdb
+            .list_databases("tenant".to_string(), Some(2), 10)
+            .await
+            .unwrap();
+        assert!(past_end.is_empty());
+        let none = sysdb
+            .list_databases("tenant".to_string(), Some(0), 0)
+            .await
+            .unwrap();
+        assert!(none.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_with_seed_and_reset() {
+        let populate = |sysdb: &mut TestSysDb| {
+            sysdb.add_collection(test_collection("tenant", "a", "collection_a"));
+            sysdb.add_collection(test_collection("tenant", "b", "collection_b"));
+        };
+        let ids = |databases: ListDatabasesResponse| {
+            databases
+                .into_iter()
+                .map(|database| (database.name, database.id))
+                .collect::<Vec<_>>()
+        };
+
+        let mut first = TestSysDb::with_seed(7);
+        populate(&mut first);
+        let first_ids = ids(first
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap());
+        let mut second = TestSysDb::with_seed(7);
+        populate(&mut second);
+        let second_ids = ids(second
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap());
+        assert_eq!(first_ids, second_ids);
+
+        first.reset();
+        assert!(first
+            .find_collections(&CollectionFilter::default())
+            .is_empty());
+        assert!(first
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap()
+            .is_empty());
+        populate(&mut first);
+        let reset_ids = ids(first
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap());
+        assert_eq!(reset_ids, first_ids);
+    }
+
+    #[tokio::test]
+    async fn test_list_databases_stable_ids() {
+        let mut sysdb = TestSysDb::new();
+        sysdb.add_collection(test_collection("tenant", "a", "collection_a"));
+        sysdb.add_collection(test_collection("tenant", "a", "collection_a2"));
+        sysdb.add_collection(test_collection("tenant", "b", "collection_b"));
+        let explicit_id = uuid::Uuid::new_v4();
+        sysdb
+            .create_database(explicit_id, "c".to_string(), "tenant".to_string())
+            .await
+            .unwrap();
+
+        let first = sysdb
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap();
+        let second = sysdb
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap();
+        let ids = |databases: &ListDatabasesResponse| {
+            databases
+                .iter()
+                .map(|database| (database.name.clone(), database.id))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&first).len(), 3);
+        assert_eq!(ids(&first), ids(&second));
+        assert_eq!(first[2].id, explicit_id);
+
+        let result = sysdb
+            .create_database(uuid::Uuid::new_v4(), "a".to_string(), "tenant".to_string())
+            .await;
+        assert!(matches!(result, Err(CreateDatabaseError::AlreadyExists(_))));
+    }
+
+    #[tokio::test]
+    async fn test_tenant_registration() {
+        let mut sysdb = TestSysDb::new();
+        let result = sysdb.get_tenant("tenant".to_string()).await;
+        assert!(matches!(result, Err(GetTenantError::NotFound(_))));
+
+        sysdb.create_tenant("tenant".to_string()).await.unwrap();
+        let tenant = sysdb.get_tenant("tenant".to_string()).await.unwrap();
+        assert_eq!(tenant.name, "tenant");
+        let tenants = sysdb
+            .get_last_compaction_time(vec!["tenant".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(tenants[0].last_compaction_time, 0);
+
+        let result = sysdb.create_tenant("tenant".to_string()).await;
+        assert!(matches!(result, Err(CreateTenantError::AlreadyExists(_))));
+
+        sysdb
+            .set_last_compaction_time("tenant".to_string(), 42)
+            .await
+            .unwrap();
+        let tenants = sysdb
+            .get_last_compaction_time(vec!["tenant".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(tenants[0].last_compaction_time, 42);
+        assert!(matches!(
+            sysdb
+                .set_last_compaction_time("missing".to_string(), 42)
+                .await,
+            Err(SetLastCompactionTimeError::TenantNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_segments_for_collection() {
+        let mut sysdb = TestSysDb::new();
+        let collection_id = CollectionUuid::new();
+        for scope in [
+            SegmentScope::VECTOR,
+            SegmentScope::METADATA,
+            SegmentScope::RECORD,
+        ] {
+            sysdb.add_segment(test_segment(collection_id, scope));
+        }
+        sysdb.add_segment(test_segment(CollectionUuid::new(), SegmentScope::VECTOR));
+
+        let segments = sysdb
+            .get_segments_for_collection(collection_id)
+            .await
+            .unwrap();
+        assert_eq!(segments.len(), 3);
+        assert!(segments.windows(2).all(|pair| pair[0].id < pair[1].id));
+        assert_eq!(sysdb.count_segments(collection_id).await.unwrap(), 3);
+
+        let unknown = CollectionUuid::new();
+        assert!(sysdb
+            .get_segments_for_collection(unknown)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(sysdb.count_segments(unknown).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_compute_collection_size_from_segments() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        sysdb.update_collection_size(collection_id, 10);
+        let record = test_segment(collection_id, SegmentScope::RECORD);
+        let metadata = test_segment(collection_id, SegmentScope::METADATA);
+        sysdb.set_segment_record_count(record.id, 7);
+        sysdb.set_segment_record_count(metadata.id, 5);
+        sysdb.add_segment(record);
+        sysdb.add_segment(metadata);
+
+        assert_eq!(sysdb.get_collection_size(collection_id).await.unwrap(), 10);
+        assert_eq!(
+            sysdb
+                .compute_collection_size_from_segments(collection_id)
+                .await
+                .unwrap(),
+            12
+        );
+        assert!(matches!(
+            sysdb
+                .compute_collection_size_from_segments(CollectionUuid::new())
+                .await,
+            Err(GetCollectionSizeError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_to_collection_events() {
+        let mut sysdb = TestSysDb::new();
+        let mut events = sysdb.subscribe();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        sysdb
+            .flush_compaction("tenant".to_string(), collection_id, 10, 0, Arc::new([]), 0)
+            .await
+            .unwrap();
+        sysdb.delete_collection(collection_id).await.unwrap();
+
+        let event = |kind| SysDbEvent {
+            collection_id,
+            kind,
+        };
+        assert_eq!(events.recv().await.unwrap(), event(SysDbEventKind::Created));
+        assert_eq!(
+            events.recv().await.unwrap(),
+            event(SysDbEventKind::Flushed { version: 1 })
+        );
+        assert_eq!(events.recv().await.unwrap(), event(SysDbEventKind::Deleted));
+        assert!(matches!(
+            events.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_collections_and_segments_are_ordered() {
+        let mut sysdb = TestSysDb::new();
+        let mut collection_ids = (0..5)
+            .map(|i| {
+                let collection = test_collection("tenant", "database", &format!("c{i}"));
+                let collection_id = collection.collection_id;
+                sysdb.add_collection(collection);
+                collection_id
+            })
+            .collect::<Vec<_>>();
+        let collection_id = collection_ids[0];
+        let mut segment_ids = (0..5)
+            .map(|_| {
+                let segment = test_segment(collection_id, SegmentScope::RECORD);
+                let segment_id = segment.id;
+                sysdb.add_segment(segment);
+                segment_id
+            })
+            .collect::<Vec<_>>();
+        collection_ids.sort();
+        segment_ids.sort();
+
+        for _ in 0..2 {
+            let collections = sysdb
+                .get_collections(None, None, None, None, None, 0)
+                .await
+                .unwrap();
+            assert_eq!(
+                collections
+                    .iter()
+                    .map(|collection| collection.collection_id)
+                    .collect::<Vec<_>>(),
+                collection_ids
+            );
+            let segments = sysdb
+                .get_segments(None, None, None, collection_id)
+                .await
+                .unwrap();
+            assert_eq!(
+                segments
+                    .iter()
+                    .map(|segment| segment.id)
+                    .collect::<Vec<_>>(),
+                segment_ids
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_soft_delete_lifecycle() {
+        let mut sysdb = TestSysDb::new();
+        let restored_id = flushed_collection(&mut sysdb, 1).await;
+        let purged_id = flushed_collection(&mut sysdb, 1).await;
+        sysdb.add_segment(test_segment(purged_id, SegmentScope::RECORD));
+        sysdb.set_now(10);
+        sysdb.delete_collection(restored_id).await.unwrap();
+        sysdb.delete_collection(purged_id).await.unwrap();
+
+        assert!(sysdb.list_soft_deleted_collections(10).is_empty());
+        assert_eq!(sysdb.list_soft_deleted_collections(11).len(), 2);
+
+        sysdb.undelete_collection(restored_id).unwrap();
+        assert!(matches!(
+            sysdb.undelete_collection(restored_id),
+            Err(DeleteCollectionError::NotFound(_))
+        ));
+        let collections = sysdb
+            .get_collections(Some(restored_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(collections[0].version, 1);
+
+        let to_gc = sysdb.list_soft_deleted_collections(11);
+        assert_eq!(to_gc.len(), 1);
+        assert_eq!(to_gc[0].collection_id, purged_id);
+        sysdb.purge_collection(purged_id).unwrap();
+        assert!(sysdb.list_soft_deleted_collections(11).is_empty());
+        assert_eq!(sysdb.count_segments(purged_id).await.unwrap(), 0);
+        assert!(sysdb.get_collection_versions(purged_id).is_empty());
+        assert!(matches!(
+            sysdb.purge_collection(restored_id),
+            Err(DeleteCollectionError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_writes_to_soft_deleted_collection_fail() {
+        let mut sysdb = TestSysDb::new();
+        let collection_id = flushed_collection(&mut sysdb, 1).await;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        sysdb.add_segment(segment.clone());
+        sysdb.delete_collection(collection_id).await.unwrap();
+
+        let request =
+            FlushCompactionRequest::new("tenant".to_string(), collection_id, 2, 1, Arc::new([]), 0);
+        assert!(matches!(
+            sysdb.flush(request.clone()).await,
+            Err(FlushCompactionError::CollectionNotFound)
+        ));
+        assert!(matches!(
+            sysdb.prepare_flush(request),
+            Err(FlushCompactionError::CollectionNotFound)
+        ));
+        assert!(matches!(
+            sysdb.cas_bump_version(collection_id, 1),
+            Err(CasError::NotFound(_))
+        ));
+        assert!(matches!(
+            sysdb.apply_segment_delta(collection_id, vec![segment.id], vec![]),
+            Err(SegmentDeltaError::CollectionNotFound(_))
+        ));
+
+        // None of the rejected writes touched the stored collection.
+        sysdb.undelete_collection(collection_id).unwrap();
+        let collections = sysdb
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(collections[0].version, 1);
+        assert_eq!(sysdb.count_segments(collection_id).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_soft_delete_through_sysdb() {
+        let mut test = TestSysDb::new();
+        let collection_id = flushed_collection(&mut test, 1).await;
+        test.set_now(10);
+        test.delete_collection(collection_id).await.unwrap();
+        let mut sysdb = SysDb::Test(test.clone());
+
+        let deleted = sysdb.list_soft_deleted_collections(11).await.unwrap();
+        assert_eq!(deleted[0].collection_id, collection_id);
+        sysdb.undelete_collection(collection_id).await.unwrap();
+        assert!(sysdb
+            .list_soft_deleted_collections(11)
+            .await
+            .unwrap()
+            .is_empty());
+
+        test.delete_collection(collection_id).await.unwrap();
+        sysdb.purge_collection(collection_id).await.unwrap();
+        assert!(matches!(
+            sysdb.undelete_collection(collection_id).await,
+            Err(DeleteCollectionError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_tenant_provisions_default_database() {
+        let mut sysdb = TestSysDb::new();
+        sysdb.create_tenant("tenant".to_string()).await.unwrap();
+        assert!(sysdb.create_tenant("tenant".to_string()).await.is_err());
+
+        let databases = sysdb
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap();
+        assert_eq!(databases.len(), 1);
+        assert_eq!(databases[0].name, "default_database");
+
+        let collection = test_collection("tenant", "default_database", "collection");
+        assert!(sysdb
+            .create_collection(collection, vec![], false)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_last_compaction_time_skips_unknown_tenants() {
+        let mut sysdb = TestSysDb::new();
+        sysdb.add_tenant_last_compaction_time("a".to_string(), 1);
+        sysdb.add_tenant_last_compaction_time("c".to_string(), 3);
+
+        let tenants = sysdb
+            .get_last_compaction_time(vec![
+                "a".to_string(),
+                "missing".to_string(),
+                "c".to_string(),
+            ])
+            .await
+            .unwrap();
+        let found = tenants
+            .into_iter()
+            .map(|tenant| (tenant.id, tenant.last_compaction_time))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![("a".to_string(), 1), ("c".to_string(), 3)]);
+
+        let tenants = sysdb
+            .get_last_compaction_time(vec!["missing".to_string()])
+            .await
+            .unwrap();
+        assert!(tenants.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_database_lifecycle() {
+        let mut sysdb = TestSysDb::new();
+        let database_id = uuid::Uuid::new_v4();
+        sysdb
+            .create_database(database_id, "empty".to_string(), "tenant".to_string())
+            .await
+            .unwrap();
+        sysdb.add_collection(test_collection("tenant", "used", "collection"));
+
+        let listed = sysdb
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 2);
+        let database = sysdb
+            .get_database("empty".to_string(), "tenant".to_string())
+            .await
+            .unwrap();
+        assert_eq!(database.id, database_id);
+        let result = sysdb
+            .get_database("empty".to_string(), "other_tenant".to_string())
+            .await;
+        assert!(matches!(result, Err(GetDatabaseError::NotFound(_))));
+
+        let result = sysdb
+            .delete_database("used".to_string(), "tenant".to_string())
+            .await;
+        assert!(matches!(result, Err(DeleteDatabaseError::NotEmpty(_))));
+        sysdb
+            .delete_database("empty".to_string(), "tenant".to_string())
+            .await
+            .unwrap();
+        let result = sysdb
+            .get_database("empty".to_string(), "tenant".to_string())
+            .await;
+        assert!(matches!(result, Err(GetDatabaseError::NotFound(_))));
+        let result = sysdb
+            .delete_database("empty".to_string(), "tenant".to_string())
+            .await;
+        assert!(matches!(result, Err(DeleteDatabaseError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_happens_before() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        sysdb.add_collection(test_collection("tenant", "database", "other"));
+        let before = sysdb.snapshot();
+        assert!(before.happens_before(&before));
+
+        sysdb
+            .flush(FlushCompactionRequest::new(
+                "tenant".to_string(),
+                collection_id,
+                1,
+                0,
+                Arc::new([]),
+                0,
+            ))
+            .await
+            .unwrap();
+        let after = sysdb.snapshot();
+
+        assert!(before.happens_before(&after));
+        assert!(!after.happens_before(&before));
+    }
+
+    #[tokio::test]
+    async fn test_collection_writes_advance_snapshot() {
+        let mut sysdb = TestSysDb::new();
+        let mut collection = test_collection("tenant", "database", "collection");
+        collection.metadata = Some(HashMap::from([(
+            "owner".to_string(),
+            MetadataValue::Str("cleanup".to_string()),
+        )]));
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection.clone());
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+
+        let mut before = sysdb.snapshot();
+        let mut advanced = |sysdb: &TestSysDb| {
+            let after = sysdb.snapshot();
+            let advanced = before.happens_before(&after) && !after.happens_before(&before);
+            before = after;
+            advanced
+        };
+
+        sysdb.cas_bump_version(collection_id, 0).unwrap();
+        assert!(advanced(&sysdb));
+        sysdb
+            .apply_segment_delta(collection_id, vec![], vec![segment.clone()])
+            .unwrap();
+        assert!(advanced(&sysdb));
+        sysdb.soft_delete_collections_by_metadata(
+            "owner".to_string(),
+            MetadataValue::Str("cleanup".to_string()),
+        );
+        assert!(advanced(&sysdb));
+        sysdb
+            .restore_from_snapshot(CollectionSnapshot {
+                collection,
+                segments: vec![segment],
+                versions: vec![],
+                overwrite: true,
+            })
+            .unwrap();
+        assert!(advanced(&sysdb));
+    }
+
+    #[tokio::test]
+    async fn test_replay_reproduces_state() {
+        let mut original = TestSysDb::new();
+        original
+            .create_database(uuid::Uuid::new_v4(), "db".to_string(), "tenant".to_string())
+            .await
+            .unwrap();
+        let collection = test_collection("tenant", "db", "collection");
+        let collection_id = collection.collection_id;
+        let segment = test_segment(collection_id, SegmentScope::RECORD);
+        let segment_id = segment.id;
+        original
+            .create_collection(collection, vec![segment], false)
+            .await
+            .unwrap();
+        original.set_now(10);
+        for version in 0..2 {
+            let flush_info = SegmentFlushInfo {
+                segment_id,
+                file_paths: HashMap::from([(
+                    "data".to_string(),
+                    vec![format!("path_{}", version)],
+                )]),
+            };
+            original
+                .flush(FlushCompactionRequest::new(
+                    "tenant".to_string(),
+                    collection_id,
+                    version as i64 + 1,
+                    version,
+                    Arc::new([flush_info]),
+                    10,
+                ))
+                .await
+                .unwrap();
+        }
+        original
+            .get_collections(Some(collection_id), None, None, None, None, 0)
+            .await
+            .unwrap();
+
+        let calls = original.call_log();
+        assert!(calls.iter().any(|call| call.is_read()));
+        let mut replayed = TestSysDb::new();
+        replayed.replay(calls).await.unwrap();
+
+        assert_eq!(
+            replayed.find_collections(&CollectionFilter::default()),
+            original.find_collections(&CollectionFilter::default())
+        );
+        assert_eq!(
+            replayed
+                .get_segments(None, None, None, collection_id)
+                .await
+                .unwrap(),
+            original
+                .get_segments(None, None, None, collection_id)
+                .await
+                .unwrap()
+        );
+        let original_databases = original
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap();
+        let replayed_databases = replayed
+            .list_databases("tenant".to_string(), None, 0)
+            .await
+            .unwrap();
+        assert_eq!(replayed_databases[0].id, original_databases[0].id);
+    }
+
+    #[test]
+    fn test_find_collections_combined_filter() {
+        let mut sysdb = TestSysDb::new();
+        let mut expected = None;
+        for (tenant, database, name, version) in [
+            ("tenant", "database", "a", 1),
+            ("tenant", "database", "b", 2),
+            ("tenant", "other", "c", 1),
+            ("other", "database", "d", 1),
+        ] {
+            let mut collection = test_collection(tenant, database, name);
+            collection.version = version;
+            if name == "a" {
+                expected = Some(collection.collection_id);
+            }
+            sysdb.add_collection(collection);
+        }
+
+        let found = sysdb.find_collections(&CollectionFilter {
+            tenant: Some("tenant".to_string()),
+            database: Some("database".to_string()),
+            version: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(found.len(), 1);
+        assert_eq!(Some(found[0].collection_id), expected);
+    }
+
+    #[test]
+    fn test_find_collections_excluding_clause() {
+        let mut sysdb = TestSysDb::new();
+        sysdb.add_collection(test_collection("tenant", "database", "a"));
+        sysdb.add_collection(test_collection("tenant", "database", "b"));
+
+        let found = sysdb.find_collections(&CollectionFilter {
+            tenant: Some("tenant".to_string()),
+            database: Some("database".to_string()),
+            version: Some(7),
+            ..Default::default()
+        });
+        assert!(found.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_collections_paging() {
+        let mut sysdb = TestSysDb::new();
+        for name in ["a", "b", "c", "d", "e"] {
+            sysdb.add_collection(test_collection("tenant", "database", name));
+        }
+        let mut ids = sysdb
+            .find_collections(&CollectionFilter::default())
+            .into_iter()
+            .map(|collection| collection.collection_id)
+            .collect::<Vec<_>>();
+        ids.sort();
+        let page = |collections: Vec<Collection>| {
+            collections
+                .into_iter()
+                .map(|collection| collection.collection_id)
+                .collect::<Vec<_>>()
+        };
+
+        let first = sysdb
+            .get_collections(None, None, None, None, Some(2), 0)
+            .await
+            .unwrap();
+        assert_eq!(page(first), ids[..2]);
+        let last = sysdb
+            .get_collections(None, None, None, None, Some(2), 4)
+            .await
+            .unwrap();
+        assert_eq!(page(last), ids[4..]);
+        let rest = sysdb
+            .get_collections(None, None, None, None, None, 1)
+            .await
+            .unwrap();
+        assert_eq!(page(rest), ids[1..]);
+        let past_end = sysdb
+            .get_collections(None, None, None, None, Some(2), 10)
+            .await
+            .unwrap();
+        assert!(past_end.is_empty());
+    }
+
+    #[test]
+    fn test_find_collections_by_metadata() {
+        let mut sysdb = TestSysDb::new();
+        let with_metadata = |name: &str, metadata: Metadata| Collection {
+            metadata: Some(metadata),
+            ..test_collection("tenant", "database", name)
+        };
+        sysdb.add_collection(with_metadata(
+            "a",
+            HashMap::from([
+                ("color".to_string(), MetadataValue::Str("red".to_string())),
+                ("size".to_string(), MetadataValue::Int(3)),
+                ("ratio".to_string(), MetadataValue::Float(0.5)),
+                ("live".to_string(), MetadataValue::Bool(true)),
+            ]),
+        ));
+        sysdb.add_collection(with_metadata(
+            "b",
+            HashMap::from([
+                ("color".to_string(), MetadataValue::Str("red".to_string())),
+                ("size".to_string(), MetadataValue::Float(3.0)),
+            ]),
+        ));
+        sysdb.add_collection(with_metadata(
+            "c",
+            HashMap::from([("color".to_string(), MetadataValue::Str("blue".to_string()))]),
+        ));
+        sysdb.add_collection(test_collection("tenant", "database", "d"));
+
+        let names = |metadata: Metadata| {
+            let mut names = sysdb
+                .find_collections(&CollectionFilter {
+                    metadata: Some(metadata),
+                    ..Default::default()
+                })
+                .into_iter()
+                .map(|collection| collection.name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names(HashMap::from([(
+                "color".to_string(),
+                MetadataValue::Str("red".to_string())
+            )])),
+            vec!["a", "b"]
+        );
+        // An int only matches an int, not an equal float.
+        assert_eq!(
+            names(HashMap::from([("size".to_string(), MetadataValue::Int(3))])),
+            vec!["a"]
+        );
+        assert_eq!(
+            names(HashMap::from([
+                ("ratio".to_string(), MetadataValue::Float(0.5)),
+                ("live".to_string(), MetadataValue::Bool(true)),
+            ])),
+            vec!["a"]
+        );
+        assert!(names(HashMap::from([(
+            "live".to_string(),
+            MetadataValue::Bool(false)
+        )]))
+        .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_collections_by_ids() {
+        let mut sysdb = TestSysDb::new();
+        let mut ids = Vec::new();
+        for name in ["a", "b", "c"] {
+            let collection = test_collection("tenant", "database", name);
+            ids.push(collection.collection_id);
+            sysdb.add_collection(collection);
+        }
+        sysdb.delete_collection(ids[2]).await.unwrap();
+
+        let found = sysdb
+            .get_collections_by_ids(vec![ids[1], CollectionUuid::new(), ids[0], ids[2], ids[1]])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|collection| collection.collection_id)
+            .collect::<Vec<_>>();
+        let mut expected = vec![ids[0], ids[1]];
+        expected.sort();
+        assert_eq!(found, expected);
+
+        // Through SysDb the results follow the input, with gaps for misses.
+        let found = SysDb::Test(sysdb)
+            .get_collections_by_ids(vec![ids[1], CollectionUuid::new(), ids[0], ids[2]])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|collection| collection.map(|collection| collection.collection_id))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![Some(ids[1]), None, Some(ids[0]), None]);
+    }
+
+    #[tokio::test]
+    async fn test_count_collections() {
+        let mut sysdb = TestSysDb::new();
+        let deleted = test_collection("tenant", "database", "a");
+        let deleted_id = deleted.collection_id;
+        sysdb.add_collection(deleted);
+        sysdb.add_collection(test_collection("tenant", "database", "b"));
+        sysdb.add_collection(test_collection("tenant", "other", "c"));
+        sysdb.add_collection(test_collection("other_tenant", "database", "d"));
+        sysdb.delete_collection(deleted_id).await.unwrap();
+
+        let count = |tenant: Option<&str>, database: Option<&str>| {
+            sysdb.count_collections(tenant.map(str::to_string), database.map(str::to_string))
+        };
+        assert_eq!(count(Some("tenant"), None).await.unwrap(), 2);
+        assert_eq!(count(Some("tenant"), Some("database")).await.unwrap(), 1);
+        assert_eq!(count(None, Some("database")).await.unwrap(), 2);
+        assert_eq!(count(None, None).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_delete_collection_tombstones() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "a");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        sysdb.add_collection(test_collection("tenant", "database", "b"));
+
+        sysdb.delete_collection(collection_id).await.unwrap();
+        let visible = sysdb
+            .get_collections(None, None, Some("tenant".to_string()), None, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(visible.len(), 1);
+        assert_ne!(visible[0].collection_id, collection_id);
+
+        let all = sysdb.find_collections(&CollectionFilter {
+            include_deleted: true,
+            ..Default::default()
+        });
+        assert_eq!(all.len(), 2);
+
+        let result = sysdb.delete_collection(collection_id).await;
+        assert!(matches!(result, Err(DeleteCollectionError::NotFound(_))));
+        let result = sysdb.delete_collection(CollectionUuid::new()).await;
+        assert!(matches!(result, Err(DeleteCollectionError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_collection_with_segments() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        for scope in [SegmentScope::RECORD, SegmentScope::METADATA] {
+            sysdb.add_segment(test_segment(collection_id, scope));
+        }
+        sysdb.add_segment(test_segment(CollectionUuid::new(), SegmentScope::VECTOR));
+
+        let (collection, segments) = sysdb.get_collection_with_segments(collection_id).unwrap();
+        assert_eq!(collection.collection_id, collection_id);
+        assert_eq!(segments.len(), 2);
+        assert!(segments
+            .iter()
+            .all(|segment| segment.collection == collection_id));
+
+        sysdb.delete_collection(collection_id).await.unwrap();
+        assert!(matches!(
+            sysdb.get_collection_with_segments(collection_id),
+            Err(GetCollectionsError::NotFound(_))
+        ));
+        assert!(matches!(
+            sysdb.get_collection_with_segments(CollectionUuid::new()),
+            Err(GetCollectionsError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_through_sysdb() {
+        let mut test = TestSysDb::new();
+        let collection_id = flushed_collection(&mut test, 1).await;
+        let mut sysdb = SysDb::Test(test.clone());
+        assert!(matches!(
+            sysdb.get_collection_with_segments(collection_id).await,
+            Err(GetCollectionWithSegmentsError::Field(_))
+        ));
+
+        for scope in [
+            SegmentScope::METADATA,
+            SegmentScope::RECORD,
+            SegmentScope::VECTOR,
+        ] {
+            test.add_segment(test_segment(collection_id, scope));
+        }
+        let collection_and_segments = sysdb
+            .get_collection_with_segments(collection_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            collection_and_segments.collection.collection_id,
+            collection_id
+        );
+        assert_eq!(
+            collection_and_segments.record_segment.scope,
+            SegmentScope::RECORD
+        );
+
+        sysdb.reset().await.unwrap();
+        assert!(matches!(
+            sysdb.get_collection_with_segments(collection_id).await,
+            Err(GetCollectionWithSegmentsError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fork_collection() {
+        let mut sysdb = TestSysDb::new();
+        let source_id = flushed_collection(&mut sysdb, 2).await;
+        let mut segment = test_segment(source_id, SegmentScope::RECORD);
+        segment.file_path = HashMap::from([("blocks".to_string(), vec!["blocks/1".to_string()])]);
+        sysdb.add_segment(segment.clone());
+
+        let fork_id = CollectionUuid::new();
+        let fork = sysdb
+            .fork_collection(source_id, fork_id, "fork".to_string())
+            .unwrap();
+        let source = sysdb.find_collections(&CollectionFilter {
+            collection_id: Some(source_id),
+            ..Default::default()
+        });
+        assert_eq!(fork.version, 2);
+        assert_eq!(fork.name, "fork");
+        assert_eq!(fork.dimension, source[0].dimension);
+        assert_eq!(fork.log_position, source[0].log_position);
+        assert_eq!(sysdb.fork_parent(fork_id), Some(source_id));
+        assert_eq!(sysdb.fork_parent(source_id), None);
+
+        let fork_segments = sysdb.get_segments(None, None, None, fork_id).await.unwrap();
+        assert_eq!(fork_segments.len(), 1);
+        assert_ne!(fork_segments[0].id, segment.id);
+        assert_eq!(fork_segments[0].file_path, segment.file_path);
+
+        let result = sysdb.fork_collection(
+            CollectionUuid::new(),
+            CollectionUuid::new(),
+            "x".to_string(),
+        );
+        assert!(matches!(result, Err(ForkCollectionError::NotFound(_))));
+        let result = sysdb.fork_collection(source_id, fork_id, "again".to_string());
+        assert!(matches!(result, Err(ForkCollectionError::AlreadyExists(_))));
+    }
+
+    #[tokio::test]
+    async fn test_segment_lifecycle() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "collection");
+        let collection_id = collection.collection_id;
+        sysdb.add_collection(collection);
+        let record = test_segment(collection_id, SegmentScope::RECORD);
+        let record_id = record.id;
+        sysdb.create_segment(record.clone()).unwrap();
+        sysdb
+            .create_segment(test_segment(collection_id, SegmentScope::METADATA))
+            .unwrap();
+        sysdb
+            .create_segment(test_segment(collection_id, SegmentScope::VECTOR))
+            .unwrap();
+
+        let result = sysdb.create_segment(record);
+        assert!(matches!(result, Err(CreateSegmentError::AlreadyExists(_))));
+
+        sysdb.delete_segment(record_id).unwrap();
+        let result = sysdb.delete_segment(record_id);
+        assert!(matches!(result, Err(DeleteSegmentError::NotFound(_))));
+
+        assert_eq!(sysdb.delete_segments_for_collection(collection_id), 2);
+        let segments = sysdb
+            .get_segments(None, None, None, collection_id)
+            .await
+            .unwrap();
+        assert!(segments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_collection() {
+        let mut sysdb = TestSysDb::new();
+        let collection = test_collection("tenant", "database", "a");
+        let collection_id = collection.collection_id;
+        let original_version = collection.version;
+        sysdb.add_collection(collection);
+        sysdb.add_collection(test_collection("tenant", "database", "b"));
+
+        let returned = sysdb
+            .update_collection(
+                collection_id,
+                Some("renamed".to_string()),
+                Some(CollectionMetadataUpdate::UpdateMetadata(HashMap::from([
+                    (
+                        "key".to_string(),
+                        UpdateMetadataValue::Str("value".to_string()),
+                    ),
+                    ("gone".to_string(), UpdateMetadataValue::None),
+                ]))),
+                Some(128),
+            )
+            .await
+            .unwrap();
+        let updated = sysdb.find_collections(&CollectionFilter {
+            collection_id: Some(collection_id),
+            ..Default::default()
+        });
+        assert_eq!(returned, updated[0]);
+        assert_eq!(updated[0].name, "renamed");
+        assert_eq!(updated[0].dimension, Some(128));
+        assert_eq!(updated[0].version, original_version);
+        assert_eq!(
+            updated[0].metadata,
+            Some(HashMap::from([(
+                "key".to_string(),
+                MetadataValue::Str("value".to_string())
+            )]))
+        );
+
+        // Only the name is touched.
+        sysdb
+            .update_collection(collection_id, Some("again".to_string()), None, None)
+            .await
+            .unwrap();
+        let updated = sysdb.find_collections(&CollectionFilter {
+            collection_id: Some(collection_id),
+            ..Default::default()
+        });
+        assert_eq!(updated[0].dimension, Some(128));
+        assert!(updated[0].metadata.is_some());
+
+        let result = sysdb
+            .update_collection(collection_id, Some("b".to_string()), None, None)
+            .await;
+        assert!(matches!(
+            result,
+            Err(UpdateCollectionError::AlreadyExists(_))
+        ));
+        let result = sysdb
+            .update_collection(CollectionUuid::new(), None, None, Some(3))
+            .await;
+        assert!(matches!(result, Err(UpdateCollectionError::NotFound(_))));
     }
 }
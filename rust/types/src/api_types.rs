@@ -41,6 +41,10 @@ pub enum GetSegmentsError {
     UnknownScope(#[from] SegmentScopeConversionError),
     #[error(transparent)]
     Internal(#[from] Box<dyn ChromaError>),
+    #[error("Segments are unavailable")]
+    Unavailable,
+    #[error("Version [{0}] of collection does not exist")]
+    VersionNotFound(i32),
 }
 
 impl ChromaError for GetSegmentsError {
@@ -49,6 +53,8 @@ impl ChromaError for GetSegmentsError {
             GetSegmentsError::SegmentConversion(_) => ErrorCodes::Internal,
             GetSegmentsError::UnknownScope(_) => ErrorCodes::Internal,
             GetSegmentsError::Internal(err) => err.code(),
+            GetSegmentsError::Unavailable => ErrorCodes::Unavailable,
+            GetSegmentsError::VersionNotFound(_) => ErrorCodes::NotFound,
         }
     }
 }
@@ -278,7 +284,7 @@ impl ChromaError for CreateDatabaseError {
     }
 }
 
-#[derive(Serialize, Debug, ToSchema)]
+#[derive(Clone, Serialize, Debug, ToSchema)]
 #[cfg_attr(feature = "pyo3", pyo3::pyclass)]
 pub struct Database {
     pub id: Uuid,
@@ -426,6 +432,8 @@ pub enum DeleteDatabaseError {
     InvalidID(String),
     #[error("Database [{0}] not found")]
     NotFound(String),
+    #[error("Database [{0}] still has collections")]
+    NotEmpty(String),
 }
 
 impl ChromaError for DeleteDatabaseError {
@@ -434,6 +442,7 @@ impl ChromaError for DeleteDatabaseError {
             DeleteDatabaseError::Internal(err) => err.code(),
             DeleteDatabaseError::InvalidID(_) => ErrorCodes::InvalidArgument,
             DeleteDatabaseError::NotFound(_) => ErrorCodes::NotFound,
+            DeleteDatabaseError::NotEmpty(_) => ErrorCodes::FailedPrecondition,
         }
     }
 }
@@ -580,6 +589,8 @@ pub enum CreateCollectionError {
     AlreadyExists(String),
     #[error("Database [{0}] does not exist")]
     DatabaseNotFound(String),
+    #[error("Database [{0}] belongs to a different tenant")]
+    DatabaseTenantMismatch(String),
     #[error("Could not fetch collections: {0}")]
     Get(#[from] GetCollectionsError),
     #[error("Could not deserialize configuration: {0}")]
@@ -595,6 +606,7 @@ impl ChromaError for CreateCollectionError {
             CreateCollectionError::InvalidSpannParameters(_) => ErrorCodes::InvalidArgument,
             CreateCollectionError::AlreadyExists(_) => ErrorCodes::AlreadyExists,
             CreateCollectionError::DatabaseNotFound(_) => ErrorCodes::InvalidArgument,
+            CreateCollectionError::DatabaseTenantMismatch(_) => ErrorCodes::InvalidArgument,
             CreateCollectionError::Get(err) => err.code(),
             CreateCollectionError::Configuration(_) => ErrorCodes::Internal,
             CreateCollectionError::Internal(err) => err.code(),
@@ -624,6 +636,10 @@ pub enum GetCollectionsError {
     Configuration(#[from] serde_json::Error),
     #[error("Could not deserialize collection ID")]
     CollectionId(#[from] uuid::Error),
+    #[error("Collection [{0}] does not exists")]
+    NotFound(String),
+    #[error("Version [{0}] of collection does not exist")]
+    VersionNotFound(i32),
 }
 
 impl ChromaError for GetCollectionsError {
@@ -632,6 +648,8 @@ impl ChromaError for GetCollectionsError {
             GetCollectionsError::Internal(err) => err.code(),
             GetCollectionsError::Configuration(_) => ErrorCodes::Internal,
             GetCollectionsError::CollectionId(_) => ErrorCodes::Internal,
+            GetCollectionsError::NotFound(_) => ErrorCodes::NotFound,
+            GetCollectionsError::VersionNotFound(_) => ErrorCodes::NotFound,
         }
     }
 }
@@ -677,6 +695,8 @@ pub enum UpdateCollectionError {
     NotFound(String),
     #[error("Metadata reset unsupported")]
     MetadataResetUnsupported,
+    #[error("Collection [{0}] already exists")]
+    AlreadyExists(String),
     #[error(transparent)]
     Internal(#[from] Box<dyn ChromaError>),
 }
@@ -686,6 +706,7 @@ impl ChromaError for UpdateCollectionError {
         match self {
             UpdateCollectionError::NotFound(_) => ErrorCodes::NotFound,
             UpdateCollectionError::MetadataResetUnsupported => ErrorCodes::InvalidArgument,
+            UpdateCollectionError::AlreadyExists(_) => ErrorCodes::AlreadyExists,
             UpdateCollectionError::Internal(err) => err.code(),
         }
     }
@@ -38,7 +38,7 @@ pub enum SegmentFlushInfoConversionError {
     DecodeError(#[from] ConversionError),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct FlushCompactionResponse {
     pub collection_id: CollectionUuid,
     pub collection_version: i32,
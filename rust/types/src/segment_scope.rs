@@ -3,7 +3,7 @@ use chroma_error::{ChromaError, ErrorCodes};
 
 use thiserror::Error;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum SegmentScope {
     VECTOR,
     METADATA,
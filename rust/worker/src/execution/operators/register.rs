@@ -221,9 +221,9 @@ mod tests {
         let segment_id_2 = SegmentUuid::from_str("00000000-0000-0000-0000-000000000004").unwrap();
         let segment_2 = Segment {
             id: segment_id_2,
-            r#type: SegmentType::HnswDistributed,
-            scope: SegmentScope::VECTOR,
-            collection: collection_uuid_2,
+            r#type: SegmentType::BlockfileMetadata,
+            scope: SegmentScope::METADATA,
+            collection: collection_uuid_1,
             metadata: None,
             file_path: file_path_2.clone(),
         };
@@ -291,19 +291,10 @@ mod tests {
             total_records_post_compaction
         );
 
-        let collection_1_segments = sysdb
+        let segments = sysdb
             .get_segments(None, None, None, collection_uuid_1)
             .await
             .unwrap();
-        let collection_2_segments = sysdb
-            .get_segments(None, None, None, collection_uuid_2)
-            .await
-            .unwrap();
-
-        let segments = collection_1_segments
-            .iter()
-            .chain(collection_2_segments.iter())
-            .collect::<Vec<&Segment>>();
 
         assert_eq!(segments.len(), 2);
         let segment_1 = segments.iter().find(|s| s.id == segment_id_1).unwrap();
@@ -1,3 +1,4 @@
+use chroma_sysdb::SysDbBackendConfig;
 use serial_test::serial;
 use worker::config::RootConfig;
 
@@ -5,5 +6,16 @@ use worker::config::RootConfig;
 #[serial]
 fn test_default_config_path() {
     // Sanity check that root config loads from default path correctly
-    let _ = RootConfig::load();
+    let config = RootConfig::load();
+    for sysdb in [
+        &config.query_service.sysdb,
+        &config.compaction_service.sysdb,
+    ] {
+        let SysDbBackendConfig::Grpc(grpc) = &sysdb.backend else {
+            panic!("Expected grpc sysdb config");
+        };
+        assert_eq!(grpc.host, "sysdb.chroma");
+        assert_eq!(grpc.port, 50051);
+        assert!(sysdb.cache.is_none());
+    }
 }